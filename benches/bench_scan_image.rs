@@ -1,10 +1,19 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use image::open;
+use histogram_text_matcher::glyphs::Glyph;
+use histogram_text_matcher::matcher::LookupMatcher;
+use histogram_text_matcher::{ColorClassifier, ColorLabel, ColorRule, HistogramType};
+use image::{open, Rgb, RgbImage};
 use std::path::PathBuf;
 
 fn criterion_benchmark(c: &mut Criterion) {
-    let image_path =
-        std::env::var("BENCH_SCAN_IMAGE").expect("BENCH_SCAN_IMAGE should be a path to an image");
+    let image_path = if let Ok(v) = std::env::var("BENCH_SCAN_IMAGE") {
+        v
+    } else {
+        println!(
+            "BENCH_SCAN_IMAGE should be a path to an image, skipping moving_windowed_histogram"
+        );
+        return;
+    };
     let image = open(image_path).expect("Failed to load file").to_rgb8();
     let glyph_set_file = std::env::var("BENCH_SCAN_GLYPH_SET")
         .expect("BENCH_SCAN_GLYPH_SET should be a path to a glyph set");
@@ -12,13 +21,12 @@ fn criterion_benchmark(c: &mut Criterion) {
         .expect("BENCH_SCAN_COLOR_LABELS should be a path to a glyph set");
 
     let glyph_path = PathBuf::from(&glyph_set_file);
-    let glyph_set = histogram_text_matcher::glyphs::load_glyph_set(&glyph_path)
+    let compiled = histogram_text_matcher::binary::load_compiled_glyph_set(&glyph_path)
         .expect(&format!("could not load glyph set at {:?}", glyph_set_file));
+    let (glyph_set, matcher) = (compiled.glyph_set(), compiled.matcher());
 
     //let image = histogram_text_matcher::image_support::rgb_image_to_view(&image);
 
-    let matcher = histogram_text_matcher::matcher::LongestGlyphMatcher::new(&glyph_set.entries);
-
     let labels =
         histogram_text_matcher::util::parse_json_labels(&color_labels).expect("invalid json");
 
@@ -27,14 +35,254 @@ fn criterion_benchmark(c: &mut Criterion) {
             let matches = histogram_text_matcher::moving_windowed_histogram(
                 &image,
                 glyph_set.line_height,
-                &matcher,
+                matcher,
+                &labels,
+            )
+            .expect("scanning should succeed");
+            black_box(matches);
+        })
+    });
+
+    #[cfg(feature = "parallel")]
+    c.bench_function("moving_windowed_histogram_parallel", |b| {
+        b.iter(|| {
+            let matches = histogram_text_matcher::moving_windowed_histogram_parallel(
+                &image,
+                glyph_set.line_height,
+                matcher,
                 &labels,
-            );
+            )
+            .expect("scanning should succeed");
             black_box(matches);
         })
     });
 }
 
+/// Create an image with pixels of the label colors and other colors in between.
+fn many_labels_image(labels: &[ColorLabel]) -> RgbImage {
+    let mut state = 0x2545f491u32;
+    let mut image = RgbImage::new(1920, 1080);
+    for p in image.pixels_mut() {
+        // Simple xorshift, to be deterministic without extra dependencies.
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        *p = if state & 3 == 0 {
+            labels[(state >> 8) as usize % labels.len()].0.color()
+        } else {
+            Rgb([(state >> 8) as u8, (state >> 16) as u8, (state >> 24) as u8])
+        };
+    }
+    image
+}
+
+/// Scan an image with many labels of exact colors. Approximate numbers on a single core, the rules
+/// in order are the scan before the classifier, measured in a separate build of the tree before it.
+/// That scan also did less work for each match and separate runs easily differ by a third:
+///
+/// | labels | rules in order | lookup table |
+/// |--------|----------------|--------------|
+/// | 1      | ~25 ms         | ~29 ms       |
+/// | 4      | ~98 ms         | ~78 ms       |
+/// | 8      | ~201 ms        | ~175 ms      |
+fn many_labels_benchmark(c: &mut Criterion) {
+    let labels = (0..8u8)
+        .map(|i| (Rgb([255 - i * 20, i * 30, 128]).into(), i as u32))
+        .collect::<Vec<ColorLabel>>();
+    let image = many_labels_image(&labels);
+    let matcher = histogram_text_matcher::matcher::LongestGlyphMatcher::new(&[]);
+
+    for count in [1, 4, 8] {
+        c.bench_function(&format!("moving_windowed_histogram_{count}_labels"), |b| {
+            b.iter(|| {
+                let matches = histogram_text_matcher::moving_windowed_histogram(
+                    &image,
+                    10,
+                    &matcher,
+                    &labels[..count],
+                )
+                .expect("scanning should succeed");
+                black_box(matches);
+            })
+        });
+    }
+}
+
+/// Compare classifying pixels with the lookup table of the classifier against evaluating the rule
+/// of each label in order, which is what the scan did before the classifier. The lookup table is
+/// built in each iteration, like a scan does once. Approximate numbers on a single core for the
+/// 1920x1080 image, both measured in the same binary:
+///
+/// | labels | rules in order | lookup table |
+/// |--------|----------------|--------------|
+/// | 1      | ~9.3 ms        | ~6.5 ms      |
+/// | 4      | ~313 ms        | ~42 ms       |
+/// | 8      | ~570 ms        | ~79 ms       |
+fn classifier_benchmark(c: &mut Criterion) {
+    let labels: Vec<ColorLabel> = vec![
+        (Rgb([255, 255, 255]).into(), 0),
+        (
+            ColorRule::Tolerance {
+                color: Rgb([200, 100, 50]),
+                tolerance: 10,
+            },
+            1,
+        ),
+        (ColorRule::lab(Rgb([30, 60, 200]), 8.0), 2),
+        (
+            ColorRule::Hsv {
+                hue: (100.0, 140.0),
+                saturation: (0.5, 1.0),
+                value: (0.5, 1.0),
+            },
+            3,
+        ),
+        (Rgb([255, 168, 0]).into(), 4),
+        (
+            ColorRule::Tolerance {
+                color: Rgb([40, 200, 40]),
+                tolerance: 20,
+            },
+            5,
+        ),
+        (ColorRule::lab(Rgb([220, 40, 180]), 5.0), 6),
+        (
+            ColorRule::Hsv {
+                hue: (200.0, 220.0),
+                saturation: (0.2, 0.6),
+                value: (0.3, 0.9),
+            },
+            7,
+        ),
+    ];
+    let image = many_labels_image(&labels);
+
+    for count in [1, 4, 8] {
+        let labels = &labels[..count];
+        c.bench_function(&format!("classify_rules_in_order_{count}_labels"), |b| {
+            b.iter(|| {
+                let mut counts = [0usize; 9];
+                for p in image.pixels() {
+                    let index = labels.iter().position(|l| l.0.matches(*p));
+                    counts[index.unwrap_or(8)] += 1;
+                }
+                black_box(counts);
+            })
+        });
+
+        c.bench_function(&format!("classify_lookup_table_{count}_labels"), |b| {
+            b.iter(|| {
+                let classifier = ColorClassifier::new(labels).expect("few labels");
+                let mut counts = [0usize; 9];
+                for p in image.pixels() {
+                    counts[classifier.classify(*p).unwrap_or(8)] += 1;
+                }
+                black_box(counts);
+            })
+        });
+    }
+}
+
+/// Scan a generated screenshot, such that the benchmark runs without any private images.
+fn synthetic_benchmark(c: &mut Criterion) {
+    use histogram_text_matcher::test_util::synthetic::{
+        generate, AlphabetRenderer, SyntheticConfig, SyntheticRenderer,
+    };
+    let renderer = AlphabetRenderer::standard();
+    let synthetic = generate(
+        &renderer,
+        &SyntheticConfig {
+            width: 1920,
+            height: 1080,
+            color_count: 4,
+            noise: 0.1,
+            ..Default::default()
+        },
+    );
+    let glyph_set = renderer.glyph_set();
+    let matcher = histogram_text_matcher::matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+    c.bench_function("moving_windowed_histogram_synthetic", |b| {
+        b.iter(|| {
+            let matches = histogram_text_matcher::moving_windowed_histogram(
+                &synthetic.image,
+                glyph_set.line_height,
+                &matcher,
+                &synthetic.labels,
+            )
+            .expect("scanning should succeed");
+            black_box(matches);
+        })
+    });
+}
+
+/// A deterministic glyph set with many glyphs, with histograms that share prefixes.
+fn many_glyphs(count: usize) -> Vec<Glyph> {
+    let mut state = 0x9e3779b9u32;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    };
+    (0..count)
+        .map(|i| {
+            let len = 3 + next() as usize % 10;
+            let hist = (0..len).map(|_| (next() % 8) as u8).collect::<Vec<u8>>();
+            Glyph::new(&hist, &format!("g{i}"))
+        })
+        .collect()
+}
+
+/// Prepare lookup trees for random glyphs and match them against all positions of a histogram.
+///
+/// Compare changes to the tree against a baseline saved before the change, with
+/// `cargo bench --bench bench_scan_image -- lookup_matcher --save-baseline before` and then
+/// `--baseline before` instead. Separate runs on a busy machine easily differ by a third, so
+/// compare layouts in a single binary, alternating between them. Approximate numbers on a single
+/// core for 500 glyphs, the nested nodes are the tree from before the flat arrays with the current
+/// glyph type. The memory excludes the 73 KB of the glyphs, which the flat trees share:
+///
+/// | tree                 | memory  | prepare | find_match |
+/// |----------------------|---------|---------|------------|
+/// | nested nodes         | 1.57 MB | ~1.5 ms | ~57 us     |
+/// | flat arrays          | 206 KB  | ~470 us | ~47 us     |
+/// | flat arrays, minimal | 32 KB   | ~200 us | ~42 us     |
+fn lookup_matcher_benchmark(c: &mut Criterion) {
+    for (count, minimal) in [(100, false), (500, false), (500, true)] {
+        let glyphs = many_glyphs(count);
+        let name = if minimal {
+            format!("{count}_minimal")
+        } else {
+            format!("{count}")
+        };
+        c.bench_function(&format!("lookup_matcher_prepare_{name}"), |b| {
+            b.iter(|| {
+                let mut matcher: LookupMatcher = Default::default();
+                matcher.prepare(&glyphs, false, minimal);
+                black_box(matcher);
+            })
+        });
+
+        let mut matcher: LookupMatcher = Default::default();
+        matcher.prepare(&glyphs, false, minimal);
+        // Histogram of all glyphs after each other, matched at every position.
+        let histogram = glyphs
+            .iter()
+            .flat_map(|g| g.hist().iter().map(|v| *v as HistogramType))
+            .collect::<Vec<_>>();
+        c.bench_function(&format!("lookup_matcher_find_match_{name}"), |b| {
+            b.iter(|| {
+                let mut found = 0;
+                for i in 0..histogram.len() {
+                    found += matcher.find_match(&histogram[i..]).is_some() as usize;
+                }
+                black_box(found);
+            })
+        });
+    }
+}
+
 fn short_warmup() -> Criterion {
     Criterion::default()
         .warm_up_time(std::time::Duration::new(5, 0))
@@ -47,4 +295,19 @@ name = benches;
 config = short_warmup();
 targets = criterion_benchmark
 );
-criterion_main!(benches);
+criterion_group!(
+name = many_labels;
+config = Criterion::default().sample_size(10);
+targets = many_labels_benchmark, classifier_benchmark
+);
+criterion_group!(
+name = synthetic;
+config = Criterion::default().sample_size(20);
+targets = synthetic_benchmark
+);
+criterion_group!(
+name = lookup_matcher;
+config = Criterion::default().sample_size(50);
+targets = lookup_matcher_benchmark
+);
+criterion_main!(benches, many_labels, synthetic, lookup_matcher);
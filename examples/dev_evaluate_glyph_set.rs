@@ -0,0 +1,36 @@
+use histogram_text_matcher::binary::load_compiled_glyph_set;
+use histogram_text_matcher::evaluation::evaluate;
+use histogram_text_matcher::training::load_collection;
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = std::env::args();
+    if args.len() <= 2 {
+        println!("expected: ./binary collection_file glyph_set_file [max_character_error_rate]");
+        println!("collection_file: Collection of annotated images, like dev_images_to_glyph_set.");
+        println!("glyph_set_file: File to load the glyph set from.");
+        println!("max_character_error_rate: Exit with an error if the rate is higher, like 0.01.");
+        std::process::exit(1);
+    }
+
+    let collection = load_collection(&PathBuf::from(std::env::args().nth(1).unwrap()))?;
+    let glyph_set = load_compiled_glyph_set(&PathBuf::from(std::env::args().nth(2).unwrap()))?;
+    let max_rate = std::env::args()
+        .nth(3)
+        .map(|v| v.parse::<f64>())
+        .transpose()?;
+
+    let report = evaluate(&collection, &glyph_set)?;
+    println!("{}", report.to_json());
+
+    if let Some(max_rate) = max_rate {
+        if report.character_error_rate > max_rate {
+            eprintln!(
+                "character error rate {} exceeds {max_rate}",
+                report.character_error_rate
+            );
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
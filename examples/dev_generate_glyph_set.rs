@@ -1,10 +1,6 @@
-use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
-use image::{Rgba, RgbaImage};
-use imageproc::drawing::draw_text_mut;
-// use imageproc::drawing::draw_text_mut;
+use ab_glyph::FontVec;
 use clap::arg;
-use histogram_text_matcher::glyphs::{Glyph, GlyphSet};
-use histogram_text_matcher::image_support::image_to_histogram;
+use histogram_text_matcher::image_support::{glyph_set_from_font, FontGlyphSetOptions};
 use std::path::{Path, PathBuf};
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let matches = clap::Command::new("generate_glyph_set")
@@ -23,6 +19,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .value_parser(clap::value_parser!(u8))
                 .default_value("255"),
         )
+        .arg(
+            clap::arg!(--"charset" <CHARSET> "Characters to render, defaults to printable ascii.")
+                .required(false),
+        )
+        .arg(
+            clap::arg!(--"ligature" <LIGATURE> "Multi-character glyph to add, may be repeated.")
+                .required(false)
+                .action(clap::ArgAction::Append),
+        )
+        .arg(clap::arg!(--"no-bearings" "Do not pad the glyphs with their side bearings."))
+        .arg(
+            clap::arg!(--"output" <DIR> "Directory to write glyph_set.{json,yaml} to.")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .default_value("/tmp/"),
+        )
         .get_matches();
 
     let font_path = matches
@@ -41,72 +52,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .expect("missing threshold");
     println!("threshold: {threshold}");
 
-    // let line_offset = 2.0 * font_size;
-
-    let useful_range = 32..127;
-
-    let scale = PxScale::from(*font_size);
-
-    let scalefont = font.as_scaled(scale);
-
-    let glyph_height = scalefont.height();
-    // let glyph_descent = scalefont.descent();
-    // let glyph_ascent = scalefont.ascent();
-
-    let mut glyph_set: GlyphSet = Default::default();
-
-    let mut tallest = 0;
-    for i in useful_range {
-        if let Some(c) = std::char::from_u32(i) {
-            let glyph_id = scalefont.glyph_id(c);
-            let glyph_h_advance = scalefont.h_advance(glyph_id);
-            let glyph_h_side_bearing = scalefont.h_side_bearing(glyph_id);
-            let w = (glyph_h_side_bearing + glyph_h_advance).ceil() as u32;
-            let h = glyph_height.ceil() as u32 + 1;
-            let mut image = RgbaImage::new(w, h);
-
-            // Render the glyph, for inspection.
-            let color = Rgba([0u8, 0u8, 0u8, 255u8]);
-            draw_text_mut(&mut image, color, 0, 0, scale, &font, &format!("{}", c));
-            image.save(format!("/tmp/glyph_{i:3>2}.png"))?;
-
-            // Extract saturated black.
-            let mut v = image.clone();
-            for p in v.pixels_mut() {
-                if p.0[3] >= *threshold {
-                    *p = Rgba([0u8, 0u8, 0u8, 255u8]);
-                } else {
-                    *p = Rgba([255u8, 255u8, 255u8, 0u8]);
-                }
-            }
-            v.save(format!("/tmp/glyph_{i:3>2}_masked.png"))?;
-
-            // Now invert it such that we get white for fonts.
-            image::imageops::colorops::invert(&mut v);
-            let row_img_gray = image::DynamicImage::ImageRgba8(v).into_luma8();
-            row_img_gray.save(format!("/tmp/glyph_{i:3>2}_gray.png"))?;
-            let row_img_histogram = image_to_histogram(&row_img_gray);
-            tallest = tallest.max(*row_img_histogram.iter().max().unwrap());
-            glyph_set
-                .entries
-                .push(Glyph::new(&row_img_histogram, &format!("{c}")));
-        }
+    let charset = matches
+        .get_one::<String>("charset")
+        .cloned()
+        .unwrap_or_else(|| (32..127u8).map(char::from).collect());
+
+    let options = FontGlyphSetOptions {
+        side_bearings: !matches.get_flag("no-bearings"),
+        ligatures: matches
+            .get_many::<String>("ligature")
+            .map(|v| v.cloned().collect())
+            .unwrap_or_default(),
+        name: font_path
+            .file_stem()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    let glyph_set = glyph_set_from_font(&font, *font_size, &charset, *threshold, &options);
+    println!(
+        "{} glyphs, line height {}",
+        glyph_set.entries.len(),
+        glyph_set.line_height
+    );
+
+    let output = matches.get_one::<PathBuf>("output").expect("has default");
+    for name in ["glyph_set.json", "glyph_set.yaml"] {
+        histogram_text_matcher::glyphs::write_glyph_set(&Path::new(output).join(name), &glyph_set)
+            .expect("writing should succeed");
     }
 
-    glyph_set.line_height = tallest as u32;
-    glyph_set.prepare();
-
-    histogram_text_matcher::glyphs::write_glyph_set(
-        &Path::new("/tmp/").join("glyph_set.json"),
-        &glyph_set,
-    )
-    .expect("writing should succeed");
-
-    histogram_text_matcher::glyphs::write_glyph_set(
-        &Path::new("/tmp/").join("glyph_set.yaml"),
-        &glyph_set,
-    )
-    .expect("writing should succeed");
-
     Ok(())
 }
@@ -9,8 +9,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .expect("No glyph set file specified.");
     let output_path = std::env::args().nth(2).expect("No output file specified.");
 
-    let glyph_set = histogram_text_matcher::glyphs::load_glyph_set(&PathBuf::from(&file_path))?;
-    let matcher = histogram_text_matcher::matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+    let compiled =
+        histogram_text_matcher::binary::load_compiled_glyph_set(&PathBuf::from(&file_path))?;
+    let (glyph_set, matcher) = (compiled.glyph_set(), compiled.matcher());
+
+    for issue in glyph_set.validate() {
+        println!("{issue}");
+    }
 
     let line_offset = 10;
     let line_height = glyph_set.line_height as i32 + line_offset;
@@ -39,7 +44,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         histogram_text_matcher::image_support::draw_histogram_mut_xy_a(
             &mut image,
             10,
-            y as u32 + (glyph_set.line_height as u32 / 2),
+            y as u32 + (glyph_set.line_height / 2),
             &g.hist(),
             Rgb([255u8, 255u8, 0u8]),
             1.0,
@@ -36,7 +36,7 @@ fn main() {
     )
     .expect("could not parse labels")
     .iter()
-    .map(|x| x.0)
+    .map(|x| x.0.color())
     .collect::<Vec<image::Rgb<u8>>>();
 
     let out_dir = std::env::args()
@@ -25,8 +25,9 @@ fn main() {
     .expect("could not parse labels");
 
     let glyph_path = PathBuf::from(&glyph_set_file);
-    let glyph_set = histogram_text_matcher::glyphs::load_glyph_set(&glyph_path)
+    let compiled = histogram_text_matcher::binary::load_compiled_glyph_set(&glyph_path)
         .expect(&format!("could not load glyph set at {:?}", glyph_set_file));
+    let (glyph_set, matcher) = (compiled.glyph_set(), compiled.matcher());
 
     let image_path = PathBuf::from(&input_image_file);
     let orig_image = open(&image_path)
@@ -55,15 +56,14 @@ fn main() {
     // let image = histogram_text_matcher::image_support::rgb_image_to_view(&orig_image);
     let image = orig_image;
 
-    let matcher = histogram_text_matcher::matcher::LongestGlyphMatcher::new(&glyph_set.entries);
-
     let now = Instant::now();
     let matches = histogram_text_matcher::moving_windowed_histogram(
         &image,
         glyph_set.line_height,
-        &matcher,
+        matcher,
         &labels,
-    );
+    )
+    .expect("scanning should succeed");
     for m in matches.iter() {
         let location = &m.location;
         print!("{location:?} -> ");
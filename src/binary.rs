@@ -0,0 +1,507 @@
+//! Compact binary format holding a glyph set together with its prepared matcher.
+//!
+//! The file starts with a header of the magic bytes, the format version, the payload length and
+//! the crc32 checksum of the payload. The payload holds the glyph set, followed by the node, leaf
+//! and tail arrays of the two lookup matchers of the [`LongestGlyphMatcher`], in which leafs refer
+//! to the entries of the glyph set by index. All integers are little endian. Loading this skips
+//! parsing text and building the trees, [`load_compiled_glyph_set`] loads any format and hands back
+//! the matcher, which is read from the file if it is binary.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::glyphs::{check_glyph_set, Glyph, GlyphSet};
+use crate::matcher::{LongestGlyphMatcher, LookupMatcher, LookupNode, Tolerance};
+use crate::{Decoder, Error, Result};
+
+/// Bytes at the start of every binary glyph set.
+pub const MAGIC: &[u8; 8] = b"HTMGLYPH";
+
+/// Version of the format, files with another version are rejected.
+pub const VERSION: u32 = 5;
+
+/// Magic, version, payload length and checksum.
+const HEADER_LEN: usize = 8 + 4 + 8 + 4;
+
+/// A glyph set with the matcher prepared from its glyphs.
+#[derive(Debug, Clone)]
+pub struct CompiledGlyphSet {
+    glyph_set: GlyphSet,
+    matcher: LongestGlyphMatcher,
+}
+
+impl CompiledGlyphSet {
+    /// Prepare the glyph set and create the matcher for it, fails if the glyph set can't be used
+    /// for matching.
+    pub fn new(mut glyph_set: GlyphSet) -> Result<Self> {
+        glyph_set.prepare();
+        check_glyph_set(&glyph_set)?;
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        Ok(CompiledGlyphSet { glyph_set, matcher })
+    }
+
+    /// Like [`CompiledGlyphSet::new`], but with the minimal lookup trees of
+    /// [`LongestGlyphMatcher::new_minimal`].
+    pub fn new_minimal(mut glyph_set: GlyphSet) -> Result<Self> {
+        glyph_set.prepare();
+        check_glyph_set(&glyph_set)?;
+        let matcher = LongestGlyphMatcher::new_minimal(&glyph_set.entries);
+        Ok(CompiledGlyphSet { glyph_set, matcher })
+    }
+
+    /// The glyph set.
+    pub fn glyph_set(&self) -> &GlyphSet {
+        &self.glyph_set
+    }
+
+    /// The matcher for the glyphs in the glyph set.
+    pub fn matcher(&self) -> &LongestGlyphMatcher {
+        &self.matcher
+    }
+
+    /// Set the tolerance of the matcher, this is stored in the binary format as well.
+    pub fn set_tolerance(&mut self, tolerance: Option<Tolerance>) {
+        self.matcher.set_tolerance(tolerance);
+    }
+
+    /// Set the decoder of the matcher, this is stored in the binary format as well.
+    pub fn set_decoder(&mut self, decoder: Decoder) {
+        self.matcher.set_decoder(decoder);
+    }
+
+    /// Split into the glyph set and the matcher.
+    pub fn into_parts(self) -> (GlyphSet, LongestGlyphMatcher) {
+        (self.glyph_set, self.matcher)
+    }
+
+    /// Serialize into the binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut payload = Writer::default();
+        payload.str(&self.glyph_set.name);
+        payload.u32(self.glyph_set.line_height);
+        payload.u32(self.glyph_set.entries.len() as u32);
+        for g in self.glyph_set.entries.iter() {
+            payload.str(g.glyph());
+            payload.bytes(g.hist());
+            let flags = g.ignore_on_lstrip() as u8
+                | (g.trim_left() as u8) << 1
+                | (g.trim_right() as u8) << 2
+                | (g.max_consecutive().is_some() as u8) << 3
+                | (g.row_profile().is_some() as u8) << 4;
+            payload.u8(flags);
+            if let Some(v) = g.max_consecutive() {
+                payload.u64(v as u64);
+            }
+            if let Some(v) = g.row_profile() {
+                payload.bytes(v);
+            }
+        }
+
+        write_matcher(&mut payload, &self.matcher.matcher);
+        write_matcher(&mut payload, &self.matcher.lstrip_matcher);
+        match self.matcher.tolerance {
+            Some(t) => {
+                payload.u8(1);
+                payload.u32(t.per_bin);
+                payload.u32(t.total);
+            }
+            None => payload.u8(0),
+        }
+        payload.u8(match self.matcher.decoder {
+            Decoder::Greedy => 0,
+            Decoder::Search => 1,
+        });
+
+        let payload = payload.0;
+        let mut res = Vec::with_capacity(HEADER_LEN + payload.len());
+        res.extend(MAGIC);
+        res.extend(VERSION.to_le_bytes());
+        res.extend((payload.len() as u64).to_le_bytes());
+        res.extend(crc32fast::hash(&payload).to_le_bytes());
+        res.extend(payload);
+        res
+    }
+
+    /// Deserialize from the binary format, verifying the version, the checksum and that the glyph
+    /// set can be used for matching.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if !is_binary(data) {
+            return Err(parse_error("missing magic bytes"));
+        }
+        if data.len() < HEADER_LEN {
+            return Err(parse_error("header is truncated"));
+        }
+        let mut header = Reader::new(&data[MAGIC.len()..HEADER_LEN]);
+        let version = header.u32()?;
+        if version != VERSION {
+            return Err(parse_error(&format!(
+                "unsupported version {version}, expected {VERSION}"
+            )));
+        }
+        let length = header.u64()?;
+        let checksum = header.u32()?;
+        let payload = &data[HEADER_LEN..];
+        if payload.len() as u64 != length {
+            return Err(parse_error(&format!(
+                "payload holds {} bytes, expected {length}",
+                payload.len()
+            )));
+        }
+        if crc32fast::hash(payload) != checksum {
+            return Err(parse_error("checksum mismatch"));
+        }
+
+        let mut r = Reader::new(payload);
+        let mut glyph_set = GlyphSet {
+            name: r.str()?,
+            line_height: r.u32()?,
+            ..Default::default()
+        };
+        let count = r.u32()?;
+        for _ in 0..count {
+            let glyph = r.str()?;
+            let hist = r.bytes()?;
+            let flags = r.u8()?;
+            let mut g = Glyph::new(hist, &glyph);
+            g.set_ignore_on_lstrip(flags & 1 != 0);
+            g.set_trim_left(flags & 2 != 0);
+            g.set_trim_right(flags & 4 != 0);
+            if flags & 8 != 0 {
+                g.set_max_consecutive(Some(r.u64()? as usize));
+            }
+            if flags & 16 != 0 {
+                g.set_row_profile(Some(r.bytes()?.to_vec()));
+            }
+            glyph_set.entries.push(g);
+        }
+        check_glyph_set(&glyph_set)?;
+
+        // Both matchers share a single copy of the glyphs.
+        let glyphs: Arc<[Glyph]> = glyph_set.entries.as_slice().into();
+        let matcher = LongestGlyphMatcher {
+            matcher: read_matcher(&mut r, &glyphs)?,
+            lstrip_matcher: read_matcher(&mut r, &glyphs)?,
+            tolerance: match r.u8()? {
+                0 => None,
+                _ => Some(Tolerance {
+                    per_bin: r.u32()?,
+                    total: r.u32()?,
+                }),
+            },
+            decoder: match r.u8()? {
+                0 => Decoder::Greedy,
+                1 => Decoder::Search,
+                v => return Err(parse_error(&format!("unknown decoder {v}"))),
+            },
+        };
+        if !r.is_empty() {
+            return Err(parse_error("trailing bytes after the matcher"));
+        }
+        Ok(CompiledGlyphSet { glyph_set, matcher })
+    }
+}
+
+/// Whether the data starts with the magic bytes of the binary format.
+pub fn is_binary(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Load a glyph set in any format together with its matcher, the matcher is only built if the
+/// file is not binary. Use this instead of [`crate::glyphs::load_glyph_set`] when matching.
+pub fn load_compiled_glyph_set(input_path: &Path) -> Result<CompiledGlyphSet> {
+    let data = std::fs::read(input_path).map_err(|e| Error::io(e, input_path))?;
+    if is_binary(&data) {
+        return CompiledGlyphSet::from_bytes(&data).map_err(|e| with_path(e, input_path));
+    }
+    CompiledGlyphSet::new(crate::glyphs::load_glyph_set(&input_path.to_owned())?)
+}
+
+/// Set the path of parse errors that don't have one.
+pub(crate) fn with_path(e: Error, input_path: &Path) -> Error {
+    match e {
+        Error::Parse {
+            path: None,
+            line,
+            message,
+        } => Error::Parse {
+            path: Some(input_path.to_owned()),
+            line,
+            message,
+        },
+        e => e,
+    }
+}
+
+fn parse_error(message: &str) -> Error {
+    Error::Parse {
+        path: None,
+        line: None,
+        message: format!("binary glyph set: {message}"),
+    }
+}
+
+fn write_matcher(w: &mut Writer, m: &LookupMatcher) {
+    w.u32(m.nodes.len() as u32);
+    for n in m.nodes.iter() {
+        w.u32(n.children_start);
+        w.u32(n.children_len);
+        w.u32(n.leafs_start);
+        w.u32(n.leafs_len);
+        w.u32(n.tail_len);
+    }
+    w.u32s(&m.leafs);
+    w.bytes(&m.tails);
+}
+
+/// Read the arrays of a lookup matcher, checking that all indices are in range. Children always
+/// come after their parent, such that the tree can't hold cycles.
+fn read_matcher(r: &mut Reader, glyphs: &Arc<[Glyph]>) -> Result<LookupMatcher> {
+    let nodes = r
+        .u32s_n(5)?
+        .chunks_exact(5)
+        .map(|v| LookupNode {
+            children_start: v[0],
+            children_len: v[1],
+            leafs_start: v[2],
+            leafs_len: v[3],
+            tail_len: v[4],
+        })
+        .collect::<Vec<LookupNode>>();
+    let leafs = r.u32s()?;
+    let tails = r.bytes()?.to_vec();
+
+    let in_range = |start: u32, len: u32, total: usize| start as usize + len as usize <= total;
+    for (i, n) in nodes.iter().enumerate() {
+        let valid = if n.tail_len != 0 {
+            // Tail nodes have no children and a single glyph.
+            n.children_len == 0
+                && n.leafs_len == 0
+                && in_range(n.children_start, n.tail_len, tails.len())
+                && in_range(n.leafs_start, 1, leafs.len())
+        } else {
+            in_range(n.children_start, n.children_len, nodes.len())
+                && in_range(n.leafs_start, n.leafs_len, leafs.len())
+                && (n.children_len == 0 || n.children_start as usize > i)
+        };
+        if !valid {
+            return Err(parse_error(&format!("node {i} is out of range")));
+        }
+    }
+    if let Some(g) = leafs.iter().find(|g| **g as usize >= glyphs.len()) {
+        return Err(parse_error(&format!("glyph index {g} out of range")));
+    }
+
+    Ok(LookupMatcher {
+        glyphs: glyphs.clone(),
+        nodes,
+        leafs,
+        tails,
+    })
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+    fn u32(&mut self, v: u32) {
+        self.0.extend(v.to_le_bytes());
+    }
+    fn u64(&mut self, v: u64) {
+        self.0.extend(v.to_le_bytes());
+    }
+    fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.0.extend(v);
+    }
+    fn u32s(&mut self, v: &[u32]) {
+        self.u32(v.len() as u32);
+        for x in v.iter() {
+            self.u32(*x);
+        }
+    }
+    fn str(&mut self, v: &str) {
+        self.bytes(v.as_bytes());
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        if self.data.len() < count {
+            return Err(parse_error("unexpected end of data"));
+        }
+        let (v, remainder) = self.data.split_at(count);
+        self.data = remainder;
+        Ok(v)
+    }
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let count = self.u32()? as usize;
+        self.take(count)
+    }
+    fn u32s(&mut self) -> Result<Vec<u32>> {
+        self.u32s_n(1)
+    }
+    /// Read a count, followed by n values for each counted element.
+    fn u32s_n(&mut self, n: usize) -> Result<Vec<u32>> {
+        let count = self.u32()? as usize;
+        let data = self.take(
+            count
+                .checked_mul(4 * n)
+                .ok_or_else(|| parse_error("too many values"))?,
+        )?;
+        Ok(data
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect())
+    }
+    fn str(&mut self) -> Result<String> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| parse_error("invalid utf-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_alphabet::standard_glyph_set;
+    use crate::Matcher;
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let mut glyph_set = standard_glyph_set();
+        glyph_set.name = String::from("standard");
+        let mut space = Glyph::new(&[0, 0, 0, 0, 0], " ");
+        space.set_max_consecutive(Some(1));
+        space.set_trim_left(true);
+        space.set_trim_right(true);
+        space.set_ignore_on_lstrip(true);
+        glyph_set.entries.push(space);
+        glyph_set.entries[1].set_row_profile(Some(vec![4, 2, 2, 5, 2, 2, 4]));
+        // A duplicate glyph, the trees refer to the first one.
+        glyph_set.entries.push(glyph_set.entries[0].clone());
+
+        let mut compiled = CompiledGlyphSet::new(glyph_set.clone()).unwrap();
+        compiled.set_tolerance(Some(Tolerance {
+            per_bin: 1,
+            total: 3,
+        }));
+        compiled.set_decoder(Decoder::Search);
+        let bytes = compiled.to_bytes();
+        assert!(is_binary(&bytes));
+
+        let loaded = CompiledGlyphSet::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.glyph_set(), compiled.glyph_set());
+        assert_eq!(loaded.matcher().matcher(), compiled.matcher().matcher());
+        assert_eq!(
+            loaded.matcher().lstrip_matcher(),
+            compiled.matcher().lstrip_matcher()
+        );
+        assert_eq!(loaded.matcher().tolerance(), compiled.matcher().tolerance());
+        assert_eq!(loaded.matcher().decoder(), Decoder::Search);
+        // The matchers share a single copy of the glyphs.
+        for m in [loaded.matcher(), compiled.matcher()] {
+            assert!(Arc::ptr_eq(&m.matcher().glyphs, &m.lstrip_matcher().glyphs));
+        }
+        let hist = [0, 5, 2, 2, 2, 5, 0];
+        assert_eq!(
+            loaded.matcher().lstrip_find_match(&hist),
+            compiled.matcher().lstrip_find_match(&hist)
+        );
+
+        // Any change is detected.
+        let mut corrupt = bytes.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            CompiledGlyphSet::from_bytes(&corrupt),
+            Err(Error::Parse { .. })
+        ));
+        let mut version = bytes.clone();
+        version[MAGIC.len()] = VERSION as u8 + 1;
+        assert!(CompiledGlyphSet::from_bytes(&version).is_err());
+        assert!(CompiledGlyphSet::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(CompiledGlyphSet::from_bytes(&bytes[..10]).is_err());
+
+        // Loading detects the format from the content, regardless of the extension.
+        let dir = std::env::temp_dir().join("histogram_text_matcher_test_binary_roundtrip");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("glyph_set.bin");
+        crate::glyphs::write_glyph_set(&path, &glyph_set).unwrap();
+        let renamed = dir.join("glyph_set.yaml");
+        std::fs::rename(&path, &renamed).unwrap();
+        assert_eq!(crate::glyphs::load_glyph_set(&renamed).unwrap(), glyph_set);
+        let loaded = load_compiled_glyph_set(&renamed).unwrap();
+        assert_eq!(loaded.matcher().matcher(), compiled.matcher().matcher());
+
+        let json = dir.join("glyph_set.json");
+        crate::glyphs::write_glyph_set(&json, &glyph_set).unwrap();
+        let loaded = load_compiled_glyph_set(&json).unwrap();
+        assert_eq!(loaded.glyph_set(), &glyph_set);
+    }
+
+    #[test]
+    fn test_binary_minimal() {
+        let glyph_set = standard_glyph_set();
+        let compiled = CompiledGlyphSet::new_minimal(glyph_set.clone()).unwrap();
+        assert!(!compiled.matcher().matcher().tails.is_empty());
+        let loaded = CompiledGlyphSet::from_bytes(&compiled.to_bytes()).unwrap();
+        assert_eq!(loaded.matcher().matcher(), compiled.matcher().matcher());
+        assert_eq!(
+            loaded.matcher().lstrip_matcher(),
+            compiled.matcher().lstrip_matcher()
+        );
+        let full = CompiledGlyphSet::new(glyph_set).unwrap();
+        assert!(full.to_bytes().len() > compiled.to_bytes().len());
+    }
+
+    #[test]
+    fn test_binary_invalid_glyph_set() {
+        let mut glyph_set = standard_glyph_set();
+        glyph_set.entries.push(Glyph::new(&[], "x"));
+        assert!(matches!(
+            CompiledGlyphSet::new(glyph_set.clone()),
+            Err(Error::InvalidGlyphSet(_))
+        ));
+
+        let dir = std::env::temp_dir().join("histogram_text_matcher_test_binary_invalid");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("glyph_set.bin");
+        let res = crate::glyphs::write_glyph_set(&path, &glyph_set);
+        assert!(matches!(res, Err(Error::InvalidGlyphSet(_))));
+
+        // A file written without the check, like one from an older version.
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        let compiled = CompiledGlyphSet { glyph_set, matcher };
+        std::fs::write(&path, compiled.to_bytes()).unwrap();
+        let res = load_compiled_glyph_set(&path);
+        assert!(matches!(res, Err(Error::InvalidGlyphSet(_))));
+        let res = crate::glyphs::load_glyph_set(&path);
+        assert!(matches!(res, Err(Error::InvalidGlyphSet(_))));
+
+        let mut glyph_set = standard_glyph_set();
+        glyph_set.line_height = 0;
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        let compiled = CompiledGlyphSet { glyph_set, matcher };
+        let res = CompiledGlyphSet::from_bytes(&compiled.to_bytes());
+        assert!(matches!(res, Err(Error::InvalidGlyphSet(_))));
+    }
+}
@@ -0,0 +1,647 @@
+//! Rules that decide whether a pixel belongs to a label.
+
+use image::Rgb;
+use std::sync::Arc;
+
+use crate::{Error, Result};
+
+/// Predicate on the color of a pixel.
+pub type ColorPredicate = Arc<dyn Fn(Rgb<u8>) -> bool + Send + Sync>;
+
+/// Rule to decide whether a pixel's color matches.
+#[derive(Clone)]
+pub enum ColorRule {
+    /// The pixel must have exactly this color.
+    Exact(Rgb<u8>),
+    /// Each channel of the pixel may differ at most tolerance from the color.
+    Tolerance { color: Rgb<u8>, tolerance: u8 },
+    /// The CIE76 distance (ΔE) in Lab space to the color must be at most max_delta_e, create this
+    /// with [`ColorRule::lab`].
+    Lab {
+        color: Rgb<u8>,
+        lab: [f32; 3],
+        max_delta_e: f32,
+    },
+    /// The pixel's hue (degrees), saturation and value (both 0.0 to 1.0) must be within the
+    /// inclusive ranges. The hue range wraps around if its start is larger than its end.
+    Hsv {
+        hue: (f32, f32),
+        saturation: (f32, f32),
+        value: (f32, f32),
+    },
+    /// The predicate decides, the color is only used to display the label.
+    Predicate {
+        color: Rgb<u8>,
+        predicate: ColorPredicate,
+    },
+}
+
+impl ColorRule {
+    /// Create a rule that matches colors within max_delta_e of the color in Lab space.
+    pub fn lab(color: Rgb<u8>, max_delta_e: f32) -> Self {
+        ColorRule::Lab {
+            color,
+            lab: rgb_to_lab(color),
+            max_delta_e,
+        }
+    }
+
+    /// Create a rule from a predicate, the color is only used to display the label.
+    pub fn predicate<F: Fn(Rgb<u8>) -> bool + Send + Sync + 'static>(
+        color: Rgb<u8>,
+        predicate: F,
+    ) -> Self {
+        ColorRule::Predicate {
+            color,
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    /// Returns whether the pixel matches this rule.
+    #[inline]
+    pub fn matches(&self, p: Rgb<u8>) -> bool {
+        match self {
+            ColorRule::Exact(color) => *color == p,
+            ColorRule::Tolerance { color, tolerance } => color
+                .0
+                .iter()
+                .zip(p.0.iter())
+                .all(|(a, b)| a.abs_diff(*b) <= *tolerance),
+            ColorRule::Lab {
+                lab, max_delta_e, ..
+            } => {
+                let other = rgb_to_lab(p);
+                let distance_squared = lab
+                    .iter()
+                    .zip(other.iter())
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum::<f32>();
+                distance_squared <= max_delta_e * max_delta_e
+            }
+            ColorRule::Hsv {
+                hue,
+                saturation,
+                value,
+            } => {
+                let [h, s, v] = rgb_to_hsv(p);
+                let hue_matches = if hue.0 <= hue.1 {
+                    h >= hue.0 && h <= hue.1
+                } else {
+                    h >= hue.0 || h <= hue.1
+                };
+                hue_matches
+                    && s >= saturation.0
+                    && s <= saturation.1
+                    && v >= value.0
+                    && v <= value.1
+            }
+            ColorRule::Predicate { predicate, .. } => predicate(p),
+        }
+    }
+
+    /// The color that represents this rule, used to display the label.
+    pub fn color(&self) -> Rgb<u8> {
+        match self {
+            ColorRule::Exact(color)
+            | ColorRule::Tolerance { color, .. }
+            | ColorRule::Lab { color, .. }
+            | ColorRule::Predicate { color, .. } => *color,
+            ColorRule::Hsv {
+                hue,
+                saturation,
+                value,
+            } => {
+                let h = if hue.0 <= hue.1 {
+                    (hue.0 + hue.1) / 2.0
+                } else {
+                    ((hue.0 + hue.1 + 360.0) / 2.0) % 360.0
+                };
+                hsv_to_rgb([
+                    h,
+                    (saturation.0 + saturation.1) / 2.0,
+                    (value.0 + value.1) / 2.0,
+                ])
+            }
+        }
+    }
+
+    /// Determine whether all colors in the inclusive box between low and high match this rule.
+    /// Returns None if some colors may match and others may not.
+    fn matches_box(&self, low: [u8; 3], high: [u8; 3]) -> Option<bool> {
+        let inside = |color: Rgb<u8>, tolerance: u8| {
+            let mut all = true;
+            for i in 0..3 {
+                let (start, end) = (
+                    color.0[i].saturating_sub(tolerance),
+                    color.0[i].saturating_add(tolerance),
+                );
+                if high[i] < start || low[i] > end {
+                    return Some(false);
+                }
+                all &= low[i] >= start && high[i] <= end;
+            }
+            if all {
+                Some(true)
+            } else {
+                None
+            }
+        };
+        // Color in the middle of the box and the number of steps from it to the furthest color in
+        // the box, for each channel.
+        let middle = Rgb([0, 1, 2].map(|i| ((low[i] as u16 + high[i] as u16) / 2) as u8));
+        let steps = [0, 1, 2].map(|i| (middle.0[i] - low[i]).max(high[i] - middle.0[i]) as f32);
+        match self {
+            ColorRule::Exact(color) => inside(*color, 0),
+            ColorRule::Tolerance { color, tolerance } => inside(*color, *tolerance),
+            ColorRule::Lab {
+                lab, max_delta_e, ..
+            } => {
+                // The distance from the middle changes at most by the bound within the box.
+                let other = rgb_to_lab(middle);
+                let distance = lab
+                    .iter()
+                    .zip(other.iter())
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum::<f32>()
+                    .sqrt();
+                let bound = (0..3).map(|i| steps[i] * LAB_MAX_STEP[i]).sum::<f32>();
+                if distance + bound <= *max_delta_e {
+                    Some(true)
+                } else if distance - bound > *max_delta_e {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            ColorRule::Hsv {
+                hue,
+                saturation,
+                value,
+            } => {
+                let (max_low, max_high) = (*low.iter().max()? as f32, *high.iter().max()? as f32);
+                let (min_low, min_high) = (*low.iter().min()? as f32, *high.iter().min()? as f32);
+                let value_state = range_state((max_low / 255.0, max_high / 255.0), *value);
+                let saturation_low = if max_low > 0.0 {
+                    ((max_low - min_high) / max_low).max(0.0)
+                } else {
+                    0.0
+                };
+                let saturation_high = if max_high > 0.0 {
+                    (max_high - min_low) / max_high
+                } else {
+                    0.0
+                };
+                let saturation_state = range_state((saturation_low, saturation_high), *saturation);
+
+                // Away from the grays the hue changes at most 60 degrees divided by the difference
+                // between the largest and smallest channel, for each step in a channel.
+                let min_delta = max_low - min_high;
+                let hue_state = if min_delta > 0.0 {
+                    let bound = 60.0 * steps.iter().sum::<f32>() / min_delta + HSV_MARGIN;
+                    let length = if hue.0 <= hue.1 {
+                        hue.1 - hue.0
+                    } else {
+                        hue.1 - hue.0 + 360.0
+                    };
+                    let start = (rgb_to_hsv(middle)[0] - bound - hue.0).rem_euclid(360.0);
+                    if start + 2.0 * bound <= length {
+                        Some(true)
+                    } else if start > length && start + 2.0 * bound < 360.0 {
+                        Some(false)
+                    } else {
+                        None
+                    }
+                } else {
+                    None
+                };
+
+                let states = [value_state, saturation_state, hue_state];
+                if states.contains(&Some(false)) {
+                    Some(false)
+                } else if states.iter().all(|s| *s == Some(true)) {
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+            ColorRule::Predicate { .. } => None,
+        }
+    }
+
+    /// Whether evaluating this rule is about as cheap as looking up its result.
+    fn is_cheap(&self) -> bool {
+        matches!(self, ColorRule::Exact(_) | ColorRule::Tolerance { .. })
+    }
+}
+
+/// Upper bound of the Lab distance between two colors that differ by one in a single channel, for
+/// each channel. Determined by checking all colors, with some margin.
+const LAB_MAX_STEP: [f32; 3] = [0.9, 1.2, 0.9];
+
+/// Margin for the rounding of the floating point HSV components.
+const HSV_MARGIN: f32 = 1e-3;
+
+/// Determine whether all values in the inclusive interval are inside the inclusive range, None if
+/// some may be and others may not.
+fn range_state(interval: (f32, f32), range: (f32, f32)) -> Option<bool> {
+    if interval.1 < range.0 - HSV_MARGIN || interval.0 > range.1 + HSV_MARGIN {
+        Some(false)
+    } else if interval.0 >= range.0 + HSV_MARGIN && interval.1 <= range.1 - HSV_MARGIN {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Number of bits of each channel used to index the lookup table of the [`ColorClassifier`].
+const CLASSIFIER_BITS: u32 = 5;
+
+/// Flag for cells of the lookup table that require the rules to be evaluated.
+const CLASSIFIER_EVALUATE: u16 = 0x8000;
+
+/// Flag for cells of the lookup table that refer to a block with the result for each color.
+const CLASSIFIER_FINE: u16 = 0x4000;
+
+/// Number of colors in a cell of the lookup table, the size of a block of the fine table.
+const CLASSIFIER_BLOCK: usize = 1 << (3 * (8 - CLASSIFIER_BITS));
+
+/// Classifies pixels to the first label whose rule they match.
+///
+/// The color space is quantised into a lookup table up front. Cells of the table that are entirely
+/// inside or outside of the rules resolve directly. Cells that are partially covered by exact or
+/// tolerance rules refer to a block that holds the result of each color in the cell. Only pixels
+/// in cells that are partially covered by the other rules evaluate the rules.
+#[derive(Clone, Debug)]
+pub struct ColorClassifier {
+    rules: Vec<ColorRule>,
+    /// Zero for no label, label index + 1, the first label to evaluate with CLASSIFIER_EVALUATE or
+    /// the block in the fine table with CLASSIFIER_FINE.
+    table: Vec<u16>,
+    /// Blocks with zero for no label or label index + 1 for each color of a cell.
+    fine: Vec<u16>,
+}
+
+impl ColorClassifier {
+    /// Create a classifier for the rules of the labels, fails if there are too many labels.
+    pub fn new(labels: &[crate::ColorLabel]) -> Result<Self> {
+        let rules = labels.iter().map(|l| l.0.clone()).collect::<Vec<_>>();
+        if rules.len() >= CLASSIFIER_FINE as usize {
+            return Err(Error::TooManyLabels {
+                count: rules.len(),
+                max: CLASSIFIER_FINE as usize - 1,
+            });
+        }
+        let cells = 1usize << CLASSIFIER_BITS;
+        let shift = 8 - CLASSIFIER_BITS;
+        let mut table = vec![0u16; cells * cells * cells];
+        let mut fine: Vec<u16> = vec![];
+        let mut states: Vec<Option<bool>> = Vec::with_capacity(rules.len());
+        for (index, entry) in table.iter_mut().enumerate() {
+            let cell = [
+                (index >> (2 * CLASSIFIER_BITS)) as u8,
+                ((index >> CLASSIFIER_BITS) % cells) as u8,
+                (index % cells) as u8,
+            ];
+            let low = cell.map(|c| c << shift);
+            let high = cell.map(|c| (c << shift) | ((1 << shift) - 1));
+
+            // Decide on the rules up to the first that covers the entire cell.
+            states.clear();
+            for rule in rules.iter() {
+                let state = rule.matches_box(low, high);
+                states.push(state);
+                if state == Some(true) {
+                    break;
+                }
+            }
+            let first = states.iter().position(|s| *s != Some(false));
+            *entry = match first {
+                None => 0,
+                Some(i) if states[i] == Some(true) => i as u16 + 1,
+                Some(i) => {
+                    // The undecided rules are cheap to evaluate for all colors of the cell.
+                    let cheap =
+                        (i..states.len()).all(|j| states[j].is_some() || rules[j].is_cheap());
+                    let block = fine.len() / CLASSIFIER_BLOCK;
+                    if cheap && block < CLASSIFIER_FINE as usize {
+                        for offset in 0..CLASSIFIER_BLOCK {
+                            let p = Rgb([
+                                low[0] | (offset >> (2 * shift)) as u8,
+                                low[1] | ((offset >> shift) % (1 << shift)) as u8,
+                                low[2] | (offset % (1 << shift)) as u8,
+                            ]);
+                            let label = (i..states.len()).find(|j| match states[*j] {
+                                Some(state) => state,
+                                None => rules[*j].matches(p),
+                            });
+                            fine.push(label.map(|j| j as u16 + 1).unwrap_or(0));
+                        }
+                        CLASSIFIER_FINE | block as u16
+                    } else {
+                        CLASSIFIER_EVALUATE | i as u16
+                    }
+                }
+            };
+        }
+        Ok(ColorClassifier { rules, table, fine })
+    }
+
+    /// The index of the first label whose rule matches the pixel.
+    #[inline]
+    pub fn classify(&self, p: Rgb<u8>) -> Option<usize> {
+        let shift = 8 - CLASSIFIER_BITS;
+        let index = ((p.0[0] as usize >> shift) << (2 * CLASSIFIER_BITS))
+            | ((p.0[1] as usize >> shift) << CLASSIFIER_BITS)
+            | (p.0[2] as usize >> shift);
+        let entry = self.table[index];
+        if entry & (CLASSIFIER_EVALUATE | CLASSIFIER_FINE) == 0 {
+            return (entry as usize).checked_sub(1);
+        }
+        if entry & CLASSIFIER_FINE != 0 {
+            let mask = (1 << shift) - 1;
+            let offset = ((p.0[0] as usize & mask) << (2 * shift))
+                | ((p.0[1] as usize & mask) << shift)
+                | (p.0[2] as usize & mask);
+            let block = (entry & !CLASSIFIER_FINE) as usize;
+            return (self.fine[block * CLASSIFIER_BLOCK + offset] as usize).checked_sub(1);
+        }
+        let start = (entry & !CLASSIFIER_EVALUATE) as usize;
+        self.rules[start..]
+            .iter()
+            .position(|r| r.matches(p))
+            .map(|i| i + start)
+    }
+}
+
+impl std::fmt::Debug for ColorRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorRule::Exact(color) => f.debug_tuple("Exact").field(color).finish(),
+            ColorRule::Tolerance { color, tolerance } => f
+                .debug_struct("Tolerance")
+                .field("color", color)
+                .field("tolerance", tolerance)
+                .finish(),
+            ColorRule::Lab {
+                color,
+                lab,
+                max_delta_e,
+            } => f
+                .debug_struct("Lab")
+                .field("color", color)
+                .field("lab", lab)
+                .field("max_delta_e", max_delta_e)
+                .finish(),
+            ColorRule::Hsv {
+                hue,
+                saturation,
+                value,
+            } => f
+                .debug_struct("Hsv")
+                .field("hue", hue)
+                .field("saturation", saturation)
+                .field("value", value)
+                .finish(),
+            ColorRule::Predicate { color, .. } => f
+                .debug_struct("Predicate")
+                .field("color", color)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl From<Rgb<u8>> for ColorRule {
+    fn from(color: Rgb<u8>) -> Self {
+        ColorRule::Exact(color)
+    }
+}
+
+/// Convert an sRGB color to CIE Lab using the D65 white point.
+pub fn rgb_to_lab(color: Rgb<u8>) -> [f32; 3] {
+    let linear = color.0.map(|c| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let [r, g, b] = linear;
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Convert a color to hue (degrees), saturation and value (both 0.0 to 1.0).
+pub fn rgb_to_hsv(color: Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = color.0.map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let h = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+    [h, s, max]
+}
+
+/// Convert hue (degrees), saturation and value (both 0.0 to 1.0) to a color.
+pub fn hsv_to_rgb(hsv: [f32; 3]) -> Rgb<u8> {
+    let [h, s, v] = hsv;
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match (h.rem_euclid(360.0) / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    Rgb([r, g, b].map(|z| ((z + m) * 255.0).round() as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_rules() {
+        let color = Rgb([200, 100, 50]);
+        let near = Rgb([203, 98, 50]);
+        let far = Rgb([220, 100, 50]);
+
+        let rule: ColorRule = color.into();
+        assert!(rule.matches(color));
+        assert!(!rule.matches(near));
+
+        let rule = ColorRule::Tolerance {
+            color,
+            tolerance: 3,
+        };
+        assert!(rule.matches(near));
+        assert!(!rule.matches(far));
+
+        let rule = ColorRule::lab(color, 3.0);
+        assert!(rule.matches(color));
+        assert!(rule.matches(near));
+        assert!(!rule.matches(far));
+        assert_eq!(rule.color(), color);
+
+        // Red hues, wrapping around zero.
+        let rule = ColorRule::Hsv {
+            hue: (340.0, 20.0),
+            saturation: (0.5, 1.0),
+            value: (0.5, 1.0),
+        };
+        assert!(rule.matches(Rgb([255, 0, 0])));
+        assert!(rule.matches(Rgb([255, 0, 40])));
+        assert!(!rule.matches(Rgb([0, 255, 0])));
+        assert!(!rule.matches(Rgb([255, 200, 200])));
+        assert_eq!(rule.color(), Rgb([191, 48, 48]));
+
+        let rule = ColorRule::predicate(color, |p| p.0[0] > 210);
+        assert!(rule.matches(far));
+        assert!(!rule.matches(color));
+        assert_eq!(rule.color(), color);
+    }
+
+    #[test]
+    fn test_classifier() {
+        let labels = vec![
+            (Rgb([255, 255, 255]).into(), 0),
+            (
+                ColorRule::Tolerance {
+                    color: Rgb([200, 100, 50]),
+                    tolerance: 20,
+                },
+                1,
+            ),
+            (Rgb([210, 100, 50]).into(), 2),
+            (ColorRule::lab(Rgb([0, 0, 255]), 10.0), 3),
+            (ColorRule::predicate(Rgb([0, 0, 0]), |p| p.0[1] == 7), 4),
+        ];
+        let classifier = ColorClassifier::new(&labels).unwrap();
+        for r in (0..=255u8).step_by(3) {
+            for g in (0..=255u8).step_by(5) {
+                for b in (0..=255u8).step_by(5) {
+                    let p = Rgb([r, g, b]);
+                    let expected = labels.iter().position(|l| l.0.matches(p));
+                    assert_eq!(classifier.classify(p), expected, "{p:?}");
+                }
+            }
+        }
+        assert_eq!(classifier.classify(Rgb([255, 255, 255])), Some(0));
+        assert_eq!(classifier.classify(Rgb([210, 100, 50])), Some(1));
+        assert_eq!(classifier.classify(Rgb([100, 7, 100])), Some(4));
+        assert_eq!(classifier.classify(Rgb([100, 8, 100])), None);
+    }
+
+    #[test]
+    fn test_classifier_boxes() {
+        let rules = [
+            ColorRule::lab(Rgb([200, 100, 50]), 10.0),
+            ColorRule::lab(Rgb([10, 20, 30]), 25.0),
+            ColorRule::Hsv {
+                hue: (340.0, 20.0),
+                saturation: (0.5, 1.0),
+                value: (0.5, 1.0),
+            },
+            ColorRule::Hsv {
+                hue: (90.0, 150.0),
+                saturation: (0.2, 0.6),
+                value: (0.1, 0.9),
+            },
+        ];
+        // A cell that is decided must hold that decision for every color in it.
+        let shift = 8 - CLASSIFIER_BITS;
+        for rule in rules.iter() {
+            let mut decided = 0;
+            for index in (0..1usize << (3 * CLASSIFIER_BITS)).step_by(13) {
+                let low = [2 * CLASSIFIER_BITS, CLASSIFIER_BITS, 0]
+                    .map(|b| (((index >> b) % (1 << CLASSIFIER_BITS)) << shift) as u8);
+                let high = low.map(|c| c | ((1 << shift) - 1));
+                let state = if let Some(state) = rule.matches_box(low, high) {
+                    state
+                } else {
+                    continue;
+                };
+                decided += 1;
+                for offset in 0..CLASSIFIER_BLOCK {
+                    let p = Rgb([
+                        low[0] | (offset >> (2 * shift)) as u8,
+                        low[1] | ((offset >> shift) % (1 << shift)) as u8,
+                        low[2] | (offset % (1 << shift)) as u8,
+                    ]);
+                    assert_eq!(rule.matches(p), state, "{rule:?} {p:?}");
+                }
+            }
+            // Most cells are far away from the boundary of the rule.
+            assert!(decided > 2000, "{rule:?} only decided {decided}");
+        }
+
+        // Only the cells near the boundary of the Lab rule evaluate, the exact color and the
+        // tolerance rule are looked up.
+        let labels = vec![
+            (Rgb([255, 255, 255]).into(), 0),
+            (
+                ColorRule::Tolerance {
+                    color: Rgb([100, 100, 50]),
+                    tolerance: 20,
+                },
+                1,
+            ),
+            (rules[0].clone(), 2),
+        ];
+        let classifier = ColorClassifier::new(&labels).unwrap();
+        let evaluated = classifier
+            .table
+            .iter()
+            .filter(|e| **e & CLASSIFIER_EVALUATE != 0)
+            .count();
+        assert!(
+            evaluated < classifier.table.len() / 20,
+            "{evaluated} cells evaluate"
+        );
+        for p in [
+            Rgb([255, 255, 255]),
+            Rgb([255, 255, 254]),
+            Rgb([80, 120, 70]),
+        ] {
+            let expected = labels.iter().position(|l| l.0.matches(p));
+            assert_eq!(classifier.classify(p), expected);
+        }
+
+        let many = (0..CLASSIFIER_FINE as usize)
+            .map(|i| (Rgb([0, 0, 0]).into(), i as u32))
+            .collect::<Vec<crate::ColorLabel>>();
+        assert!(matches!(
+            ColorClassifier::new(&many),
+            Err(Error::TooManyLabels { .. })
+        ));
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(rgb_to_hsv(Rgb([0, 0, 255])), [240.0, 1.0, 1.0]);
+        assert_eq!(hsv_to_rgb([240.0, 1.0, 1.0]), Rgb([0, 0, 255]));
+        let [l, a, b] = rgb_to_lab(Rgb([255, 255, 255]));
+        assert!((l - 100.0).abs() < 0.1 && a.abs() < 0.1 && b.abs() < 0.1);
+    }
+}
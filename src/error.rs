@@ -0,0 +1,133 @@
+//! Error type used by the fallible functions in this crate.
+
+use image::Rgb;
+use std::path::PathBuf;
+
+/// Errors that can be returned by this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing failed, holds the path involved if there was one.
+    Io {
+        source: std::io::Error,
+        path: Option<PathBuf>,
+    },
+    /// Parsing failed, holds the path and line of the problem if known.
+    Parse {
+        path: Option<PathBuf>,
+        line: Option<usize>,
+        message: String,
+    },
+    /// The extension of the path does not denote a supported file format.
+    UnsupportedExtension(PathBuf),
+    /// The glyph set can't be used for matching.
+    InvalidGlyphSet(String),
+    /// Multiple labels use the same color.
+    DuplicateColorLabel(Rgb<u8>),
+    /// The window to slide over the image is taller than the image itself.
+    WindowTallerThanImage { window_size: u32, image_height: u32 },
+    /// More labels than the color classifier supports.
+    TooManyLabels { count: usize, max: usize },
+    /// Decoding an image failed, holds the path of the image if there was one.
+    Image {
+        source: image::ImageError,
+        path: Option<PathBuf>,
+    },
+    /// An annotated training image does not agree with its annotation.
+    InvalidAnnotation { name: String, reason: String },
+}
+
+/// Result type with this crate's error.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Create an io error that happened for the provided path.
+    pub(crate) fn io(source: std::io::Error, path: &std::path::Path) -> Self {
+        Error::Io {
+            source,
+            path: Some(path.to_owned()),
+        }
+    }
+
+    /// Create a parse error from a json error.
+    pub(crate) fn json(e: serde_json::Error, path: Option<&std::path::Path>) -> Self {
+        Error::Parse {
+            path: path.map(|p| p.to_owned()),
+            line: Some(e.line()),
+            message: e.to_string(),
+        }
+    }
+
+    /// Create a parse error from a yaml error.
+    pub(crate) fn yaml(e: serde_yaml::Error, path: Option<&std::path::Path>) -> Self {
+        Error::Parse {
+            path: path.map(|p| p.to_owned()),
+            line: e.location().map(|l| l.line()),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io { source, path } => match path {
+                Some(path) => write!(f, "{}: {source}", path.display()),
+                None => write!(f, "{source}"),
+            },
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => {
+                if let Some(path) = path {
+                    write!(f, "{}", path.display())?;
+                    if let Some(line) = line {
+                        write!(f, ":{line}")?;
+                    }
+                    write!(f, ": ")?;
+                }
+                write!(f, "parse error: {message}")
+            }
+            Error::UnsupportedExtension(path) => {
+                write!(f, "unsupported file extension: {}", path.display())
+            }
+            Error::InvalidGlyphSet(reason) => write!(f, "invalid glyph set: {reason}"),
+            Error::DuplicateColorLabel(color) => {
+                write!(f, "color {:?} is used by multiple labels", color.0)
+            }
+            Error::WindowTallerThanImage {
+                window_size,
+                image_height,
+            } => write!(
+                f,
+                "window of {window_size} rows is taller than the image of {image_height} rows"
+            ),
+            Error::TooManyLabels { count, max } => {
+                write!(f, "{count} labels is more than the supported {max}")
+            }
+            Error::Image { source, path } => match path {
+                Some(path) => write!(f, "{}: {source}", path.display()),
+                None => write!(f, "{source}"),
+            },
+            Error::InvalidAnnotation { name, reason } => {
+                write!(f, "invalid annotation for {name}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Error::Io { source, path: None }
+    }
+}
@@ -0,0 +1,360 @@
+//! Evaluating how well a glyph set recognises the text in annotated images.
+//!
+//! The images are scanned with the full [`moving_windowed_histogram`] pipeline, the matches in the
+//! region of interest of each annotation are aligned with the annotated text and the differences
+//! are collected in an [`EvaluationReport`].
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use image::{Rgb, RgbImage};
+use serde::Serialize;
+
+use crate::binary::CompiledGlyphSet;
+use crate::training::{image_name, load_image, Collection};
+use crate::{moving_windowed_histogram, ColorLabel, Error, Match2D, Rect, Result};
+
+/// The result for a single annotation.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ImageEvaluation {
+    /// Name of the image.
+    pub name: String,
+    /// The annotated text.
+    pub expected: String,
+    /// The text of the matches in the region of interest, separated by spaces.
+    pub found: String,
+    /// Number of character insertions, deletions and substitutions between the texts.
+    pub errors: usize,
+}
+
+/// An annotation for which no match was found.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct MissedMatch {
+    pub name: String,
+    pub expected: String,
+    pub roi: Rect,
+}
+
+/// A match that is not in the region of interest of any annotation with its color.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExtraMatch {
+    pub name: String,
+    pub found: String,
+    pub location: Rect,
+}
+
+/// Results of the evaluation of a glyph set.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct EvaluationReport {
+    /// Number of errors divided by the number of annotated characters.
+    pub character_error_rate: f64,
+    /// Number of annotated characters.
+    pub characters: usize,
+    /// Number of character insertions, deletions and substitutions.
+    pub errors: usize,
+    /// Counts of the found character for each expected character, an empty string denotes a
+    /// character that was missing or inserted.
+    pub confusion: BTreeMap<String, BTreeMap<String, usize>>,
+    /// The result for each annotation.
+    pub images: Vec<ImageEvaluation>,
+    /// Annotations without any match.
+    pub missed: Vec<MissedMatch>,
+    /// Matches outside of the annotations.
+    pub extra: Vec<ExtraMatch>,
+}
+
+impl EvaluationReport {
+    /// Convert the report to pretty printed json.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report can always be serialized")
+    }
+}
+
+/// Align the found text to the expected text with the minimum number of edits.
+///
+/// Each entry holds the expected and the found character, either is None for a character that is
+/// missing or was inserted.
+pub fn align(expected: &str, found: &str) -> Vec<(Option<char>, Option<char>)> {
+    let a = expected.chars().collect::<Vec<char>>();
+    let b = found.chars().collect::<Vec<char>>();
+    // Edit distance between the prefixes of both texts.
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, v) in d[0].iter_mut().enumerate() {
+        *v = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = d[i - 1][j - 1] + (a[i - 1] != b[j - 1]) as usize;
+            d[i][j] = substitution.min(d[i - 1][j] + 1).min(d[i][j - 1] + 1);
+        }
+    }
+
+    let mut res = vec![];
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && d[i][j] == d[i - 1][j - 1] + (a[i - 1] != b[j - 1]) as usize {
+            res.push((Some(a[i - 1]), Some(b[j - 1])));
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && d[i][j] == d[i - 1][j] + 1 {
+            res.push((Some(a[i - 1]), None));
+            i -= 1;
+        } else {
+            res.push((None, Some(b[j - 1])));
+            j -= 1;
+        }
+    }
+    res.reverse();
+    res
+}
+
+/// Evaluate the glyph set on the annotated images of the collection, loaded from disk.
+pub fn evaluate(collection: &Collection, glyph_set: &CompiledGlyphSet) -> Result<EvaluationReport> {
+    let mut images: BTreeMap<PathBuf, RgbImage> = Default::default();
+    for annotated in collection.images.iter() {
+        if let Entry::Vacant(entry) = images.entry(collection.image_path(annotated)) {
+            let image = load_image(entry.key())?;
+            entry.insert(image);
+        }
+    }
+    evaluate_images(collection, glyph_set, &images)
+}
+
+/// Evaluate the glyph set on the annotated images of the collection, the images are looked up by
+/// [`Collection::image_path`].
+pub fn evaluate_images(
+    collection: &Collection,
+    glyph_set: &CompiledGlyphSet,
+    images: &BTreeMap<PathBuf, RgbImage>,
+) -> Result<EvaluationReport> {
+    let matcher = glyph_set.matcher();
+    let glyph_set = glyph_set.glyph_set();
+    let mut report: EvaluationReport = Default::default();
+    let normalize = |text: &str| {
+        if collection.drop_space {
+            text.replace(' ', "")
+        } else {
+            text.to_owned()
+        }
+    };
+
+    // Scan each image once, with all colors of its annotations.
+    let mut annotations_by_path: BTreeMap<PathBuf, Vec<usize>> = Default::default();
+    for (i, annotated) in collection.images.iter().enumerate() {
+        annotations_by_path
+            .entry(collection.image_path(annotated))
+            .or_default()
+            .push(i);
+    }
+
+    let mut evaluations = vec![None; collection.images.len()];
+    for (path, indices) in annotations_by_path.iter() {
+        let name = image_name(path);
+        let image = images.get(path).ok_or_else(|| Error::InvalidAnnotation {
+            name: name.clone(),
+            reason: format!("no image provided for {}", path.display()),
+        })?;
+
+        let mut labels: Vec<ColorLabel> = vec![];
+        for i in indices.iter() {
+            let (r, g, b) = collection.images[*i].color;
+            let color = Rgb([r, g, b]);
+            if !labels.iter().any(|l| l.0.color() == color) {
+                labels.push((color.into(), labels.len() as u32));
+            }
+        }
+        let matches = moving_windowed_histogram(image, glyph_set.line_height, matcher, &labels)?;
+
+        let mut assigned = vec![false; matches.len()];
+        for i in indices.iter() {
+            let annotated = &collection.images[*i];
+            let (r, g, b) = annotated.color;
+            let color = Rgb([r, g, b]);
+            let mut in_roi = matches
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| {
+                    labels[m.tokens[0].label as usize].0.color() == color
+                        && center_in(&m.location, &annotated.roi)
+                })
+                .collect::<Vec<(usize, &Match2D)>>();
+            in_roi.sort_by_key(|(_, m)| m.location.x);
+            for (index, _) in in_roi.iter() {
+                assigned[*index] = true;
+            }
+
+            let expected = normalize(&annotated.text);
+            let found = normalize(
+                &in_roi
+                    .iter()
+                    .map(|(_, m)| m.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+            if in_roi.is_empty() {
+                report.missed.push(MissedMatch {
+                    name: name.clone(),
+                    expected: expected.clone(),
+                    roi: annotated.roi.clone(),
+                });
+            }
+
+            let mut errors = 0;
+            for (e, f) in align(&expected, &found) {
+                errors += (e != f) as usize;
+                *report
+                    .confusion
+                    .entry(e.map(String::from).unwrap_or_default())
+                    .or_default()
+                    .entry(f.map(String::from).unwrap_or_default())
+                    .or_default() += 1;
+            }
+            report.characters += expected.chars().count();
+            report.errors += errors;
+            evaluations[*i] = Some(ImageEvaluation {
+                name: name.clone(),
+                expected,
+                found,
+                errors,
+            });
+        }
+
+        for (m, _) in matches.iter().zip(assigned.iter()).filter(|(_, a)| !**a) {
+            report.extra.push(ExtraMatch {
+                name: name.clone(),
+                found: m.to_string(),
+                location: m.location.clone(),
+            });
+        }
+    }
+
+    report.images = evaluations.into_iter().flatten().collect();
+    report.character_error_rate = if report.characters == 0 {
+        0.0
+    } else {
+        report.errors as f64 / report.characters as f64
+    };
+    Ok(report)
+}
+
+/// Whether the center of the location is inside the region of interest, which uses its width and
+/// height as size.
+fn center_in(location: &Rect, roi: &Rect) -> bool {
+    let x = location.x + location.w / 2;
+    let y = location.y + location.h / 2;
+    x >= roi.x && x < roi.x + roi.w && y >= roi.y && y < roi.y + roi.h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_alphabet::render_standard_color;
+    use crate::training::{build_glyph_set_from_samples, AnnotatedImage, Sample};
+
+    #[test]
+    fn test_align() {
+        assert_eq!(
+            align("abc", "adcx"),
+            vec![
+                (Some('a'), Some('a')),
+                (Some('b'), Some('d')),
+                (Some('c'), Some('c')),
+                (None, Some('x')),
+            ]
+        );
+        assert_eq!(
+            align("ab", "b"),
+            vec![(Some('a'), None), (Some('b'), Some('b'))]
+        );
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let color = Rgb([255u8, 255, 255]);
+        let red = Rgb([255u8, 0, 0]);
+        let annotation = |y: u32, text: &str| AnnotatedImage {
+            file_path: String::from("screen.png"),
+            roi: Rect {
+                x: 0,
+                y,
+                w: 100,
+                h: 11,
+            },
+            text: text.to_owned(),
+            color: (255, 255, 255),
+        };
+        // Train a glyph set without the letter e.
+        let mut training_image = RgbImage::new(100, 40);
+        render_standard_color(&mut training_image, 3, 2, "abc", color);
+        render_standard_color(&mut training_image, 3, 12, "dwa", color);
+        let training = Collection {
+            images: vec![annotation(0, "abc"), annotation(10, "dwa")],
+            ..Default::default()
+        };
+        let samples = training
+            .images
+            .iter()
+            .map(|a| Sample::from_image(&training_image, a, "train", false).unwrap())
+            .collect::<Vec<_>>();
+        let (glyph_set, _) = build_glyph_set_from_samples(&training, &samples).unwrap();
+        let glyph_set = CompiledGlyphSet::new(glyph_set).unwrap();
+
+        let mut image = RgbImage::new(100, 60);
+        render_standard_color(&mut image, 3, 2, "cab", color);
+        render_standard_color(&mut image, 3, 42, "bad", color);
+        render_standard_color(&mut image, 60, 42, "ab", color);
+        // Not matched with the annotated color, so it is ignored.
+        render_standard_color(&mut image, 60, 2, "ab", red);
+        let collection = Collection {
+            images: vec![
+                annotation(0, "cab"),
+                // Nothing was drawn here.
+                annotation(20, "ab"),
+                // Only the first half of the image.
+                AnnotatedImage {
+                    roi: Rect {
+                        x: 0,
+                        y: 40,
+                        w: 50,
+                        h: 11,
+                    },
+                    ..annotation(40, "bed")
+                },
+            ],
+            ..Default::default()
+        };
+        let images = BTreeMap::from([(PathBuf::from("screen.png"), image)]);
+        let report = evaluate_images(&collection, &glyph_set, &images).unwrap();
+
+        assert_eq!(report.images.len(), 3);
+        assert_eq!(report.images[0].found, "cab");
+        assert_eq!(report.images[0].errors, 0);
+        assert_eq!(report.images[1].found, "");
+        assert_eq!(report.images[1].errors, 2);
+        assert_eq!(report.images[2].found, "bad");
+        assert_eq!(report.images[2].errors, 1);
+        assert_eq!(report.characters, 8);
+        assert_eq!(report.errors, 3);
+        assert_eq!(report.character_error_rate, 0.375);
+        assert_eq!(report.confusion["b"]["b"], 2);
+        assert_eq!(report.confusion["a"][""], 1);
+        assert_eq!(report.confusion["e"]["a"], 1);
+
+        assert_eq!(report.missed.len(), 1);
+        assert_eq!(report.missed[0].expected, "ab");
+        assert_eq!(report.extra.len(), 1);
+        assert_eq!(report.extra[0].found, "ab");
+        assert_eq!(report.extra[0].location.x, 61);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["errors"], 3);
+        assert_eq!(json["missed"][0]["name"], "screen");
+
+        let res = evaluate_images(&collection, &glyph_set, &Default::default());
+        assert!(matches!(res, Err(Error::InvalidAnnotation { .. })));
+    }
+}
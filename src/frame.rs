@@ -0,0 +1,529 @@
+//! Incremental scanning of successive frames.
+//!
+//! When scanning a stream of frames most of the image usually stays the same between frames. The
+//! [`FrameScanner`] keeps the classified pixels of each row and the matches found for each window
+//! position of the previous frame. Only the rows that changed are classified again, and only for
+//! the windows that contain changed rows the histograms are built and matched again. Deciding on
+//! the overlapping matches still walks every window position of the frame, but only over the
+//! stored matches, without touching the pixels. Each frame is still read once to hash its rows,
+//! unless the changed rows are provided with [`FrameScanner::scan_dirty`].
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use image::{GenericImageView, Pixel};
+
+use crate::glyphs::Glyph;
+use crate::{
+    check_unique_labels, classify_pixel, decide_on_matches, finalize_considerations,
+    window_matches, ColorClassifier, ColorLabel, Confidence, Error, HistogramType, LabelledGlyph,
+    LabelledHistogram, Match2D, Matcher, Rect, Result,
+};
+
+/// The matches that changed between two frames.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameChanges<'a> {
+    /// Matches that are present in this frame, but were not in the previous one.
+    pub added: Vec<Match2D<'a>>,
+    /// Matches that were present in the previous frame, but are no longer.
+    pub removed: Vec<Match2D<'a>>,
+}
+
+impl FrameChanges<'_> {
+    /// Returns true if no matches were added or removed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A token of a stored match, the glyph is an index into the glyph store.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct StoredToken {
+    glyph: u32,
+    label: u32,
+    location: Rect,
+}
+
+/// A match that is kept between frames, without borrowing the matcher.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct StoredMatch {
+    tokens: Vec<StoredToken>,
+    location: Rect,
+    bounds: Rect,
+    confidence: Confidence,
+}
+
+/// Copies of the glyphs that were matched, the stored matches refer to these.
+#[derive(Debug, Clone, Default)]
+struct GlyphStore {
+    glyphs: Vec<Glyph>,
+    indices: HashMap<Glyph, u32>,
+}
+
+impl GlyphStore {
+    /// Index of the glyph in the store, the glyph is added if it is not present yet.
+    fn intern(&mut self, glyph: &Glyph) -> u32 {
+        if let Some(index) = self.indices.get(glyph) {
+            return *index;
+        }
+        let index = self.glyphs.len() as u32;
+        self.glyphs.push(glyph.clone());
+        self.indices.insert(glyph.clone(), index);
+        index
+    }
+
+    /// Convert a match to one that refers to the glyphs in the store, the cache holds the index of
+    /// each glyph of the matcher that was interned before.
+    fn store(&mut self, m: &Match2D, cache: &mut HashMap<*const Glyph, u32>) -> StoredMatch {
+        let tokens = m
+            .tokens
+            .iter()
+            .map(|t| StoredToken {
+                glyph: *cache
+                    .entry(t.glyph as *const Glyph)
+                    .or_insert_with(|| self.intern(t.glyph)),
+                label: t.label,
+                location: t.location.clone(),
+            })
+            .collect();
+        StoredMatch {
+            tokens,
+            location: m.location.clone(),
+            bounds: m.bounds.clone(),
+            confidence: m.confidence,
+        }
+    }
+
+    /// Convert a stored match back into a match.
+    fn load(&self, m: &StoredMatch) -> Match2D<'_> {
+        Match2D {
+            tokens: m
+                .tokens
+                .iter()
+                .map(|t| LabelledGlyph {
+                    glyph: &self.glyphs[t.glyph as usize],
+                    label: t.label,
+                    location: t.location.clone(),
+                })
+                .collect(),
+            location: m.location.clone(),
+            bounds: m.bounds.clone(),
+            confidence: m.confidence,
+        }
+    }
+}
+
+/// Stateful scanner that reuses the work done for rows that did not change since the last frame.
+///
+/// Matches are kept between frames, these refer to copies of the matched glyphs held by the
+/// scanner, such that the scanner can own the matcher.
+#[derive(Debug, Clone)]
+pub struct FrameScanner<M: Matcher> {
+    matcher: M,
+    labels: Vec<ColorLabel>,
+    classifier: ColorClassifier,
+    window_size: u32,
+    /// Dimensions of the previous frame.
+    dimensions: (u32, u32),
+    /// Hash of each row of the previous frame.
+    row_hashes: Vec<u64>,
+    /// The pixels in each row that matched a label, as (x, label index).
+    row_pixels: Vec<Vec<(u32, usize)>>,
+    /// Matches found at each window position in the previous frame.
+    window_results: Vec<Option<Vec<StoredMatch>>>,
+    /// The final matches of the previous frame.
+    matches: Vec<StoredMatch>,
+    /// The glyphs the stored matches refer to.
+    glyphs: GlyphStore,
+}
+
+impl<M: Matcher> FrameScanner<M> {
+    /// Create a new frame scanner, fails if the colors of the labels are not unique.
+    pub fn new(matcher: M, window_size: u32, labels: &[ColorLabel]) -> Result<Self> {
+        check_unique_labels(labels)?;
+        Ok(FrameScanner {
+            matcher,
+            labels: labels.to_vec(),
+            classifier: ColorClassifier::new(labels)?,
+            window_size,
+            dimensions: (0, 0),
+            row_hashes: vec![],
+            row_pixels: vec![],
+            window_results: vec![],
+            matches: vec![],
+            glyphs: Default::default(),
+        })
+    }
+
+    /// The matcher used to find the glyphs.
+    pub fn matcher(&self) -> &M {
+        &self.matcher
+    }
+
+    /// The matches of the most recently scanned frame.
+    pub fn matches(&self) -> Vec<Match2D<'_>> {
+        self.matches.iter().map(|m| self.glyphs.load(m)).collect()
+    }
+
+    /// Forget the previous frame, the next scan processes the entire image.
+    pub fn reset(&mut self) {
+        self.dimensions = (0, 0);
+        self.row_hashes.clear();
+        self.row_pixels.clear();
+        self.window_results.clear();
+        self.matches.clear();
+    }
+
+    /// Scan a frame, rows that changed are found by comparing them against the previous frame.
+    pub fn scan<I: GenericImageView>(&mut self, image: &I) -> Result<FrameChanges<'_>>
+    where
+        <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+    {
+        let full = self.prepare(image)?;
+        let mut changed = vec![false; image.height() as usize];
+        for y in 0..image.height() {
+            let hash = row_hash(image, y);
+            if full || self.row_hashes[y as usize] != hash {
+                self.row_hashes[y as usize] = hash;
+                self.row_pixels[y as usize] = self.row_pixels(image, y);
+                changed[y as usize] = true;
+            }
+        }
+        Ok(self.update(&changed))
+    }
+
+    /// Scan a frame, only rows that intersect the dirty rectangles are considered changed.
+    ///
+    /// If the dimensions of the frame differ from the previous frame the entire frame is scanned.
+    pub fn scan_dirty<I: GenericImageView>(
+        &mut self,
+        image: &I,
+        dirty: &[Rect],
+    ) -> Result<FrameChanges<'_>>
+    where
+        <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+    {
+        let full = self.prepare(image)?;
+        let mut changed = vec![full; image.height() as usize];
+        for r in dirty.iter() {
+            let end = std::cmp::min(r.top() as usize + 1, changed.len());
+            for c in changed.iter_mut().take(end).skip(r.bottom() as usize) {
+                *c = true;
+            }
+        }
+        for y in 0..image.height() {
+            if changed[y as usize] {
+                // Keep the hashes up to date, such that scan can be used on the next frame.
+                self.row_hashes[y as usize] = row_hash(image, y);
+                self.row_pixels[y as usize] = self.row_pixels(image, y);
+            }
+        }
+        Ok(self.update(&changed))
+    }
+
+    /// Check the frame and reset the state if its dimensions changed, returns true if the entire
+    /// frame must be processed.
+    fn prepare<I: GenericImageView>(&mut self, image: &I) -> Result<bool> {
+        if self.window_size > image.height() {
+            return Err(Error::WindowTallerThanImage {
+                window_size: self.window_size,
+                image_height: image.height(),
+            });
+        }
+        if self.dimensions == image.dimensions() {
+            return Ok(false);
+        }
+        let height = image.height() as usize;
+        self.dimensions = image.dimensions();
+        self.row_hashes = vec![0; height];
+        self.row_pixels = vec![vec![]; height];
+        self.window_results = vec![None; height - self.window_size as usize + 1];
+        Ok(true)
+    }
+
+    /// Classify the pixels of a row, the first label with a matching color wins.
+    fn row_pixels<I: GenericImageView>(&self, image: &I, y: u32) -> Vec<(u32, usize)>
+    where
+        <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+    {
+        let mut res = vec![];
+        for x in 0..image.width() {
+            if let Some(index) = classify_pixel(&self.classifier, &image.get_pixel(x, y)) {
+                res.push((x, index));
+            }
+        }
+        res
+    }
+
+    /// Slide the window over the rows, matching only the windows that contain changed rows.
+    fn update(&mut self, changed: &[bool]) -> FrameChanges<'_> {
+        let width = self.dimensions.0 as usize;
+        let window_size = self.window_size as usize;
+        let mut histograms = self
+            .labels
+            .iter()
+            .map(|l| LabelledHistogram {
+                past_histograms: Default::default(),
+                histogram: vec![0; width],
+                label: l.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        // Number of changed rows before each row, to quickly check windows for changes.
+        let mut changed_before = vec![0usize; changed.len() + 1];
+        for (y, c) in changed.iter().enumerate() {
+            changed_before[y + 1] = changed_before[y] + *c as usize;
+        }
+
+        // Index in the glyph store of the glyphs of the matcher seen during this update.
+        let mut cache: HashMap<*const Glyph, u32> = HashMap::new();
+        // The window position the histograms hold, they are only built for the windows that are
+        // matched again, from the classified pixels of their rows.
+        let mut current: Option<usize> = None;
+        for y in 0..self.window_results.len() {
+            let window_changed = changed_before[y + window_size] != changed_before[y];
+            if window_changed || self.window_results[y].is_none() {
+                match current {
+                    Some(c) if c + 1 == y => {
+                        apply_row(&mut histograms, &self.row_pixels[y - 1], false);
+                        apply_row(&mut histograms, &self.row_pixels[y + window_size - 1], true);
+                    }
+                    _ => {
+                        if let Some(c) = current {
+                            for row in self.row_pixels[c..c + window_size].iter() {
+                                apply_row(&mut histograms, row, false);
+                            }
+                        }
+                        for row in self.row_pixels[y..y + window_size].iter() {
+                            apply_row(&mut histograms, row, true);
+                        }
+                    }
+                }
+                current = Some(y);
+
+                let window = &self.row_pixels[y..y + window_size];
+                let rows = |index: usize, x: u32, width: u32| {
+                    window
+                        .iter()
+                        .map(|row| {
+                            row.iter()
+                                .filter(|(px, i)| *i == index && *px >= x && *px < x + width)
+                                .count() as HistogramType
+                        })
+                        .collect()
+                };
+                let matches_2d = window_matches(
+                    y as u32,
+                    self.window_size,
+                    &histograms,
+                    &self.matcher,
+                    &rows,
+                );
+                self.window_results[y] = Some(
+                    matches_2d
+                        .iter()
+                        .map(|m| self.glyphs.store(m, &mut cache))
+                        .collect(),
+                );
+            }
+        }
+
+        // Decide on the overlapping matches of all windows, like moving_windowed_histogram does.
+        let glyphs = &self.glyphs;
+        let mut res_final: Vec<Match2D> = Vec::new();
+        let mut res_consider: VecDeque<Match2D> = VecDeque::new();
+        for (y, window_result) in self.window_results.iter().enumerate() {
+            let matches_2d = window_result
+                .as_ref()
+                .expect("populated above")
+                .iter()
+                .map(|m| glyphs.load(m))
+                .collect();
+            decide_on_matches(matches_2d, &mut res_consider);
+            finalize_considerations(y as u32, &mut res_consider, &mut res_final);
+        }
+        res_final.extend(res_consider.drain(..));
+
+        // All glyphs of the final matches are in the store, so this doesn't add any.
+        let current = res_final
+            .iter()
+            .map(|m| StoredMatch {
+                tokens: m
+                    .tokens
+                    .iter()
+                    .map(|t| StoredToken {
+                        glyph: glyphs.indices[t.glyph],
+                        label: t.label,
+                        location: t.location.clone(),
+                    })
+                    .collect(),
+                location: m.location.clone(),
+                bounds: m.bounds.clone(),
+                confidence: m.confidence,
+            })
+            .collect::<Vec<_>>();
+        let previous = std::mem::replace(&mut self.matches, current);
+        let previous_set = previous.iter().collect::<HashSet<_>>();
+        let current_set = self.matches.iter().collect::<HashSet<_>>();
+        FrameChanges {
+            added: self
+                .matches
+                .iter()
+                .filter(|m| !previous_set.contains(m))
+                .map(|m| glyphs.load(m))
+                .collect(),
+            removed: previous
+                .iter()
+                .filter(|m| !current_set.contains(m))
+                .map(|m| glyphs.load(m))
+                .collect(),
+        }
+    }
+}
+
+/// Add the classified pixels of a row to the histograms of their labels, or remove them.
+fn apply_row(histograms: &mut [LabelledHistogram], row: &[(u32, usize)], add: bool) {
+    for (x, index) in row.iter() {
+        let bin = &mut histograms[*index].histogram[*x as usize];
+        if add {
+            *bin += 1;
+        } else {
+            *bin -= 1;
+        }
+    }
+}
+
+/// Hash the pixels of a row, with the multiply and rotate hash from rustc's FxHasher. Rows are
+/// hashed for every frame, so this has to be fast rather than resistant to collisions.
+fn row_hash<I: GenericImageView>(image: &I, y: u32) -> u64
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+    let mut hash: u64 = 0;
+    for x in 0..image.width() {
+        let pixel = image
+            .get_pixel(x, y)
+            .channels()
+            .iter()
+            .fold(0u64, |v, c| (v << 8) | *c as u64);
+        hash = (hash.rotate_left(5) ^ pixel).wrapping_mul(SEED);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyphs::{Glyph, GlyphSet};
+    use crate::matcher::LongestGlyphMatcher;
+    use crate::moving_windowed_histogram;
+    use crate::test_util::test_alphabet::{render_standard_color, standard_alphabet};
+    use image::{Rgb, RgbImage};
+
+    fn glyph_set() -> GlyphSet {
+        let (glyph_image, glyph_text) = standard_alphabet();
+        let mut glyph_set = crate::image_support::dev_image_to_glyph_set(
+            &glyph_image,
+            Some(0),
+            &[Rgb::<u8>([255, 255, 255])],
+            &None,
+        );
+        for (i, c) in glyph_text.chars().enumerate() {
+            let old_glyph = &glyph_set.entries[i];
+            glyph_set.entries[i] = Glyph::new(old_glyph.hist(), &String::from(c));
+        }
+        glyph_set.prepare();
+        glyph_set
+    }
+
+    fn sorted(mut matches: Vec<Match2D>) -> Vec<Match2D> {
+        matches.sort_by(|a, b| a.location.partial_cmp(&b.location).unwrap());
+        matches
+    }
+
+    #[test]
+    fn test_frame_scanner() {
+        let glyph_set = glyph_set();
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        let white = Rgb::<u8>([255, 255, 255]);
+        let red = Rgb::<u8>([255, 0, 0]);
+        let labels = vec![(white.into(), 0), (red.into(), 1)];
+        let window_size = glyph_set.line_height;
+
+        let mut image = RgbImage::new(100, 60);
+        render_standard_color(&mut image, 10, 10, "caab", red);
+        render_standard_color(&mut image, 50, 13, "deeb", white);
+
+        let mut scanner = FrameScanner::new(matcher.clone(), window_size, &labels).unwrap();
+        let changes = scanner.scan(&image).unwrap();
+        let expected = moving_windowed_histogram(&image, window_size, &matcher, &labels).unwrap();
+        assert_eq!(changes.added.len(), 2);
+        assert!(changes.removed.is_empty());
+        assert_eq!(sorted(scanner.matches()), sorted(expected));
+
+        // An identical frame doesn't change anything, also after moving the scanner, which owns the
+        // matcher.
+        let changes = scanner.scan(&image).unwrap();
+        assert!(changes.is_empty());
+        let mut scanner = Box::new(scanner);
+        let changes = scanner.scan(&image).unwrap();
+        assert!(changes.is_empty());
+
+        // Add text below the existing text, only that is reported.
+        render_standard_color(&mut image, 10, 40, "wacb", white);
+        let changes = scanner.scan(&image).unwrap();
+        assert!(changes.removed.is_empty());
+        assert_eq!(changes.added.len(), 1);
+        assert_eq!(changes.added[0].to_string(), "wacb");
+        let expected = moving_windowed_histogram(&image, window_size, &matcher, &labels).unwrap();
+        assert_eq!(sorted(scanner.matches()), sorted(expected));
+
+        // Changes in separate places of one frame.
+        render_standard_color(&mut image, 80, 12, "c", red);
+        render_standard_color(&mut image, 80, 45, "e", white);
+        let changes = scanner.scan(&image).unwrap();
+        assert!(changes.removed.is_empty());
+        let mut added = changes
+            .added
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>();
+        added.sort();
+        assert_eq!(added, vec!["c", "e"]);
+        let expected = moving_windowed_histogram(&image, window_size, &matcher, &labels).unwrap();
+        assert_eq!(sorted(scanner.matches()), sorted(expected));
+
+        // Remove the first text, using a dirty rectangle.
+        let mut cleared = image.clone();
+        for y in 5..25 {
+            for x in 0..45 {
+                cleared.put_pixel(x, y, Rgb([0, 0, 0]));
+            }
+        }
+        let dirty = Rect {
+            x: 0,
+            y: 5,
+            w: 44,
+            h: 19,
+        };
+        let changes = scanner.scan_dirty(&cleared, &[dirty]).unwrap();
+        assert!(changes.added.is_empty());
+        assert_eq!(changes.removed.len(), 1);
+        assert_eq!(changes.removed[0].to_string(), "caab");
+        let expected = moving_windowed_histogram(&cleared, window_size, &matcher, &labels).unwrap();
+        assert_eq!(sorted(scanner.matches()), sorted(expected));
+
+        // Changes outside the dirty rectangles are not seen.
+        let changes = scanner.scan_dirty(&image, &[]).unwrap();
+        assert!(changes.is_empty());
+
+        // A frame with different dimensions is processed entirely.
+        let small = RgbImage::new(50, 20);
+        let changes = scanner.scan(&small).unwrap();
+        assert_eq!(changes.removed.len(), 4);
+        assert!(scanner.matches().is_empty());
+
+        let res = scanner.scan(&RgbImage::new(50, 3));
+        assert!(matches!(res, Err(Error::WindowTallerThanImage { .. })));
+    }
+}
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use serde_yaml;
 
+use crate::error::{Error, Result};
+
 type HistogramValue = u8;
 
 /// Representation for a single glyph.
@@ -44,6 +46,11 @@ pub struct Glyph {
     /// Denotes whether the character is to be trimmed from the left side from matches.
     #[serde(default)]
     trim_left: bool,
+
+    /// Number of pixels in each row of the line, from the top of the line. Matches of this glyph
+    /// are rejected if the pixels in their window don't have this profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    row_profile: Option<Vec<HistogramValue>>,
 }
 
 impl Glyph {
@@ -59,6 +66,7 @@ impl Glyph {
             max_consecutive: None,
             trim_right: false,
             trim_left: false,
+            row_profile: None,
         };
         z.prepare();
         z
@@ -101,6 +109,11 @@ impl Glyph {
         Some(&self.lstrip_hist)
     }
 
+    /// Whether this glyph is excluded from lstripped matching.
+    pub fn ignore_on_lstrip(&self) -> bool {
+        self.ignore_on_lstrip
+    }
+
     /// Set the ignore on lstrip variable to the provided state.
     pub fn set_ignore_on_lstrip(&mut self, ignore: bool) {
         self.ignore_on_lstrip = ignore;
@@ -142,6 +155,33 @@ impl Glyph {
     pub fn set_trim_left(&mut self, trim_left: bool) {
         self.trim_left = trim_left;
     }
+
+    /// The number of pixels in each row of the line, if the glyph has a row profile.
+    pub fn row_profile(&self) -> Option<&[HistogramValue]> {
+        self.row_profile.as_deref()
+    }
+
+    /// Set the row profile used to verify matches of this glyph.
+    pub fn set_row_profile(&mut self, row_profile: Option<Vec<HistogramValue>>) {
+        self.row_profile = row_profile;
+    }
+
+    /// Whether the pixels counted in each row of a window match the row profile, rows beyond
+    /// either of them count as empty. Always true if the glyph has no row profile.
+    pub fn matches_row_profile(&self, rows: &[crate::HistogramType]) -> bool {
+        let profile = if let Some(profile) = self.row_profile.as_ref() {
+            profile
+        } else {
+            return true;
+        };
+        (0..std::cmp::max(profile.len(), rows.len())).all(|i| {
+            profile
+                .get(i)
+                .map(|v| *v as crate::HistogramType)
+                .unwrap_or(0)
+                == rows.get(i).copied().unwrap_or(0)
+        })
+    }
 }
 
 /// GlyphSet holds a collection of glyphs and associated data.
@@ -159,6 +199,68 @@ pub struct GlyphSet {
     pub name: String,
 }
 
+/// A problem found in a glyph set by GlyphSet::validate, glyphs are referred to by their index in
+/// the entries. Where stripped is true, the problem applies to the lstripped histograms.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValidationIssue {
+    /// These glyphs have identical histograms, only the first one can ever be matched.
+    IdenticalHistograms { glyphs: Vec<usize>, stripped: bool },
+    /// The histogram of this glyph is the start of the histograms of longer glyphs, the longer
+    /// glyphs are preferred whenever they match, so this glyph may be unreachable.
+    PrefixOfLonger {
+        glyph: usize,
+        longer: Vec<usize>,
+        stripped: bool,
+    },
+    /// A bin in the histogram holds more pixels than fit in the line height.
+    BinTallerThanLineHeight { glyph: usize, bin: usize, value: u8 },
+    /// The histogram of this glyph is empty.
+    EmptyHistogram { glyph: usize },
+    /// Glyphs with the same string that differ in their trim_left or trim_right setting.
+    ConflictingTrim { glyphs: Vec<usize> },
+    /// The histogram only holds zeros but ignore_on_lstrip is not set, its lstripped histogram is
+    /// empty.
+    ZeroGlyphWithoutIgnoreOnLstrip { glyph: usize },
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = |stripped: &bool| if *stripped { "lstripped " } else { "" };
+        match self {
+            ValidationIssue::IdenticalHistograms { glyphs, stripped } => {
+                write!(
+                    f,
+                    "glyphs {glyphs:?} have identical {}histograms",
+                    kind(stripped)
+                )
+            }
+            ValidationIssue::PrefixOfLonger {
+                glyph,
+                longer,
+                stripped,
+            } => write!(
+                f,
+                "{}histogram of glyph {glyph} is the start of glyphs {longer:?}",
+                kind(stripped)
+            ),
+            ValidationIssue::BinTallerThanLineHeight { glyph, bin, value } => write!(
+                f,
+                "bin {bin} of glyph {glyph} holds {value}, more than the line height"
+            ),
+            ValidationIssue::EmptyHistogram { glyph } => {
+                write!(f, "glyph {glyph} has an empty histogram")
+            }
+            ValidationIssue::ConflictingTrim { glyphs } => {
+                write!(f, "glyphs {glyphs:?} are the same but have different trims")
+            }
+            ValidationIssue::ZeroGlyphWithoutIgnoreOnLstrip { glyph } => write!(
+                f,
+                "glyph {glyph} only holds zeros but doesn't set ignore_on_lstrip"
+            ),
+        }
+    }
+}
+
 impl GlyphSet {
     /// Prepare the glyph set for use.
     pub fn prepare(&mut self) {
@@ -166,33 +268,156 @@ impl GlyphSet {
             entry.prepare();
         }
     }
-}
 
-/// Load a glyph set from a json or yaml file.
-pub fn load_glyph_set(input_path: &PathBuf) -> Result<GlyphSet, Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::Read;
-    let mut file = File::open(input_path).map_err(|e| format!("{e:?}:{input_path:?}"))?;
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
+    /// Check the glyph set for problems that affect matching, these are the same situations that
+    /// are highlighted in red and magenta by LookupMatcher::to_dot. The glyph set should be
+    /// prepared.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues: Vec<ValidationIssue> = vec![];
+
+        for (i, g) in self.entries.iter().enumerate() {
+            if g.hist().is_empty() {
+                issues.push(ValidationIssue::EmptyHistogram { glyph: i });
+                continue;
+            }
+            for (bin, value) in g.hist().iter().enumerate() {
+                if *value as u32 > self.line_height {
+                    issues.push(ValidationIssue::BinTallerThanLineHeight {
+                        glyph: i,
+                        bin,
+                        value: *value,
+                    });
+                }
+            }
+            if g.total() == 0 && !g.ignore_on_lstrip {
+                issues.push(ValidationIssue::ZeroGlyphWithoutIgnoreOnLstrip { glyph: i });
+            }
+        }
 
-    let extension = input_path.extension().expect("should have an extension");
+        // Check identical histograms and histograms that are the start of longer ones, for both
+        // the full histograms and the lstripped histograms. Empty histograms are reported above.
+        for stripped in [false, true] {
+            let hists = self
+                .entries
+                .iter()
+                .map(|g| {
+                    if stripped {
+                        g.lstrip_hist()
+                    } else {
+                        Some(g.hist())
+                    }
+                })
+                .map(|h| h.filter(|h| !h.is_empty()))
+                .collect::<Vec<_>>();
+
+            let mut identical_seen = vec![false; hists.len()];
+            for (i, a) in hists.iter().enumerate() {
+                let a = if let Some(a) = a { a } else { continue };
+                let mut identical = vec![i];
+                let mut longer = vec![];
+                for (j, b) in hists.iter().enumerate() {
+                    let b = if let Some(b) = b { b } else { continue };
+                    if i == j {
+                        continue;
+                    }
+                    if a == b {
+                        identical.push(j);
+                    } else if b.starts_with(a) {
+                        longer.push(j);
+                    }
+                }
+                if identical.len() > 1 && !identical_seen[i] {
+                    identical.sort();
+                    for j in identical.iter() {
+                        identical_seen[*j] = true;
+                    }
+                    issues.push(ValidationIssue::IdenticalHistograms {
+                        glyphs: identical,
+                        stripped,
+                    });
+                }
+                if !longer.is_empty() {
+                    issues.push(ValidationIssue::PrefixOfLonger {
+                        glyph: i,
+                        longer,
+                        stripped,
+                    });
+                }
+            }
+        }
+
+        // Check that glyphs with the same string are trimmed the same way.
+        let mut by_string: Vec<(&str, Vec<usize>)> = vec![];
+        for (i, g) in self.entries.iter().enumerate() {
+            match by_string.iter_mut().find(|(s, _)| *s == g.glyph()) {
+                Some((_, indices)) => indices.push(i),
+                None => by_string.push((g.glyph(), vec![i])),
+            }
+        }
+        for (_, indices) in by_string {
+            let first = &self.entries[indices[0]];
+            let conflicts = indices.iter().any(|i| {
+                let g = &self.entries[*i];
+                g.trim_left != first.trim_left || g.trim_right != first.trim_right
+            });
+            if conflicts {
+                issues.push(ValidationIssue::ConflictingTrim { glyphs: indices });
+            }
+        }
+
+        issues
+    }
+}
+
+/// Load a glyph set from a json, yaml or binary file. Binary files are recognised by their
+/// content, the others by their extension. The matcher stored in a binary file is dropped, use
+/// [`crate::binary::load_compiled_glyph_set`] to keep it.
+pub fn load_glyph_set(input_path: &PathBuf) -> Result<GlyphSet> {
+    let data = std::fs::read(input_path).map_err(|e| Error::io(e, input_path))?;
 
     let mut p: GlyphSet;
+    if crate::binary::is_binary(&data) {
+        p = crate::binary::CompiledGlyphSet::from_bytes(&data)
+            .map_err(|e| crate::binary::with_path(e, input_path))?
+            .into_parts()
+            .0;
+        return Ok(p);
+    }
+    let content = String::from_utf8(data).map_err(|e| Error::Parse {
+        path: Some(input_path.clone()),
+        line: None,
+        message: e.to_string(),
+    })?;
+
+    let extension = input_path.extension().unwrap_or_default();
+
     if extension == "json" {
-        p = serde_json::from_str(&content)?;
+        p = serde_json::from_str(&content).map_err(|e| Error::json(e, Some(input_path)))?;
     } else if extension == "yaml" {
-        p = serde_yaml::from_str(&content)?;
+        p = serde_yaml::from_str(&content).map_err(|e| Error::yaml(e, Some(input_path)))?;
     } else {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Unknown type",
-        )));
+        return Err(Error::UnsupportedExtension(input_path.clone()));
     }
     p.prepare();
+    check_glyph_set(&p)?;
     Ok(p)
 }
 
+/// Check that a glyph set can be used for matching.
+pub(crate) fn check_glyph_set(set: &GlyphSet) -> Result<()> {
+    if set.line_height == 0 {
+        return Err(Error::InvalidGlyphSet(String::from("line height is zero")));
+    }
+    // An empty histogram would match everywhere without advancing.
+    if let Some(g) = set.entries.iter().find(|g| g.hist().is_empty()) {
+        return Err(Error::InvalidGlyphSet(format!(
+            "glyph {:?} has an empty histogram",
+            g.glyph()
+        )));
+    }
+    Ok(())
+}
+
 fn to_yaml_string(set: &GlyphSet) -> String {
     let mut s = String::new();
     s.push_str(&format!("name: \"{}\"\n", set.name));
@@ -221,6 +446,12 @@ fn to_yaml_string(set: &GlyphSet) -> String {
             if entry.trim_right {
                 s.push_str("    trim_right: true\n");
             }
+            if let Some(row_profile) = entry.row_profile.as_ref() {
+                s.push_str(&format!(
+                    "    row_profile: {}\n",
+                    serde_json::to_string(row_profile).unwrap()
+                ));
+            }
         }
     } else {
         s.push_str(&format!("entries: []\n"));
@@ -228,33 +459,33 @@ fn to_yaml_string(set: &GlyphSet) -> String {
     s
 }
 
-/// Write a glyph set to a json or yaml file.
-pub fn write_glyph_set(
-    output_path: &PathBuf,
-    set: &GlyphSet,
-) -> Result<(), Box<dyn std::error::Error>> {
+/// Write a glyph set to a json, yaml or binary file, the binary format is used for the bin
+/// extension and includes the prepared matcher. Fails if the glyph set can't be used for matching.
+pub fn write_glyph_set(output_path: &PathBuf, set: &GlyphSet) -> Result<()> {
     use std::fs::File;
     use std::io::Write;
 
     // https://doc.rust-lang.org/std/path/struct.Path.html#method.ends_with
     // Yikes, that's a footgun.
 
-    let extension = output_path.extension().expect("should have an extension");
+    let extension = output_path.extension().unwrap_or_default();
     let s;
-    if extension == "json" {
-        s = serde_json::to_string(&set)?;
+    if extension == "bin" {
+        s = crate::binary::CompiledGlyphSet::new(set.clone())?.to_bytes();
+    } else if extension == "json" {
+        s = serde_json::to_string(&set)
+            .map_err(|e| Error::json(e, Some(output_path)))?
+            .into_bytes();
     } else if extension == "yaml" {
         // Instead of relying on serde_yaml, we manually conver the glyph set here to ensure
         // newlines are convenient.
-        s = to_yaml_string(&set);
+        s = to_yaml_string(&set).into_bytes();
     } else {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Unknown type: {:?}", output_path),
-        )));
+        return Err(Error::UnsupportedExtension(output_path.clone()));
     }
-    let mut file = File::create(output_path)?;
-    file.write(s.as_bytes())?;
+    check_glyph_set(set)?;
+    let mut file = File::create(output_path).map_err(|e| Error::io(e, output_path))?;
+    file.write_all(&s).map_err(|e| Error::io(e, output_path))?;
 
     Ok(())
 }
@@ -286,8 +517,121 @@ mod tests {
             glyph: String::from("ba"),
             ..Default::default()
         });
+        set.entries.push(Glyph {
+            hist: vec![0, 3, 1, 0],
+            glyph: String::from("\"b"),
+            ignore_on_lstrip: true,
+            row_profile: Some(vec![1, 2, 1]),
+            ..Default::default()
+        });
         let as_yaml = to_yaml_string(&set);
         let res: GlyphSet = serde_yaml::from_str(&as_yaml).unwrap();
         assert_eq!(res, set);
     }
+
+    #[test]
+    fn test_load_glyph_set_errors() {
+        let dir = std::env::temp_dir().join("histogram_text_matcher_test_load_glyph_set_errors");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let missing = dir.join("does_not_exist.yaml");
+        let res = load_glyph_set(&missing);
+        assert!(matches!(res, Err(Error::Io { path: Some(p), .. }) if p == missing));
+
+        let unsupported = dir.join("glyph_set.txt");
+        std::fs::write(&unsupported, "").unwrap();
+        let res = load_glyph_set(&unsupported);
+        assert!(matches!(res, Err(Error::UnsupportedExtension(_))));
+        let res = write_glyph_set(&unsupported, &Default::default());
+        assert!(matches!(res, Err(Error::UnsupportedExtension(_))));
+
+        let malformed = dir.join("malformed.yaml");
+        std::fs::write(&malformed, "name: \"a\"\nline_height: 7\nentries: [[\n").unwrap();
+        let res = load_glyph_set(&malformed);
+        assert!(matches!(res, Err(Error::Parse { line: Some(_), .. })));
+
+        let empty_glyph = dir.join("empty_glyph.json");
+        let set = GlyphSet {
+            line_height: 7,
+            entries: vec![Glyph::new(&[], "a")],
+            ..Default::default()
+        };
+        let res = write_glyph_set(&empty_glyph, &set);
+        assert!(matches!(res, Err(Error::InvalidGlyphSet(_))));
+        std::fs::write(&empty_glyph, serde_json::to_string(&set).unwrap()).unwrap();
+        let res = load_glyph_set(&empty_glyph);
+        assert!(matches!(res, Err(Error::InvalidGlyphSet(_))));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut space = Glyph::new(&[0, 0, 0], " ");
+        space.set_trim_left(true);
+        let mut set = GlyphSet {
+            line_height: 5,
+            entries: vec![
+                Glyph::new(&[0, 5, 3, 0], "a"),
+                Glyph::new(&[0, 5, 3, 0], "b"),
+                Glyph::new(&[0, 5, 3, 0, 2], "c"),
+                Glyph::new(&[5, 3, 0], "d"),
+                Glyph::new(&[0, 7, 0], "e"),
+                Glyph::new(&[], "f"),
+                space,
+                Glyph::new(&[0, 0, 0, 0], " "),
+            ],
+            ..Default::default()
+        };
+        set.prepare();
+        let issues = set.validate();
+        let expected = vec![
+            ValidationIssue::BinTallerThanLineHeight {
+                glyph: 4,
+                bin: 1,
+                value: 7,
+            },
+            ValidationIssue::EmptyHistogram { glyph: 5 },
+            ValidationIssue::ZeroGlyphWithoutIgnoreOnLstrip { glyph: 6 },
+            ValidationIssue::ZeroGlyphWithoutIgnoreOnLstrip { glyph: 7 },
+            ValidationIssue::IdenticalHistograms {
+                glyphs: vec![0, 1],
+                stripped: false,
+            },
+            ValidationIssue::PrefixOfLonger {
+                glyph: 0,
+                longer: vec![2],
+                stripped: false,
+            },
+            ValidationIssue::PrefixOfLonger {
+                glyph: 1,
+                longer: vec![2],
+                stripped: false,
+            },
+            ValidationIssue::PrefixOfLonger {
+                glyph: 6,
+                longer: vec![7],
+                stripped: false,
+            },
+            ValidationIssue::IdenticalHistograms {
+                glyphs: vec![0, 1, 3],
+                stripped: true,
+            },
+            ValidationIssue::PrefixOfLonger {
+                glyph: 0,
+                longer: vec![2],
+                stripped: true,
+            },
+            ValidationIssue::PrefixOfLonger {
+                glyph: 1,
+                longer: vec![2],
+                stripped: true,
+            },
+            ValidationIssue::PrefixOfLonger {
+                glyph: 3,
+                longer: vec![2],
+                stripped: true,
+            },
+            ValidationIssue::ConflictingTrim { glyphs: vec![6, 7] },
+        ];
+        assert_eq!(issues, expected);
+    }
 }
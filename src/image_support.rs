@@ -6,8 +6,9 @@ use imageproc::rect::Rect;
 
 use std::path::Path;
 
-use ab_glyph::{Font, PxScale};
+use ab_glyph::{point, Font, GlyphId, PxScale, ScaleFont};
 use imageproc::drawing::draw_text_mut;
+use std::collections::BTreeSet;
 
 use crate::glyphs::{Glyph, GlyphSet};
 
@@ -139,6 +140,178 @@ pub fn render_font_image<F: Font>(
     image
 }
 
+/// Options for [`glyph_set_from_font`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontGlyphSetOptions {
+    /// Pad the histograms with the side bearings, such that consecutive glyphs fit together.
+    pub side_bearings: bool,
+    /// Multi-character strings that are rendered and stored as a single glyph, like ligatures.
+    pub ligatures: Vec<String>,
+    /// Name of the resulting glyph set.
+    pub name: String,
+}
+
+impl Default for FontGlyphSetOptions {
+    fn default() -> Self {
+        FontGlyphSetOptions {
+            side_bearings: true,
+            ligatures: vec![],
+            name: String::new(),
+        }
+    }
+}
+
+/// Thresholded ink of a string rendered with its pen starting at zero.
+struct RenderedInk {
+    /// Column of the first histogram bin, relative to the pen start.
+    offset: i32,
+    /// Number of ink pixels per column, empty if nothing was drawn.
+    hist: Histogram,
+    /// Number of ink pixels per row of the line, pixels outside the line are not counted.
+    rows: Histogram,
+    /// Horizontal advance of the pen over the entire string.
+    advance: f32,
+}
+
+/// Pixels with a coverage of at least threshold / 255 of a string rendered with the same layout
+/// as draw_text_mut, relative to the pen start at the top of the line, and the advance of the pen.
+pub(crate) fn font_ink<F: Font>(
+    font: &F,
+    scale: PxScale,
+    text: &str,
+    threshold: u8,
+) -> (BTreeSet<(i32, i32)>, f32) {
+    let scalefont = font.as_scaled(scale);
+    // Glyphs in a ligature may overlap, so collect the pixels before counting them.
+    let mut ink: BTreeSet<(i32, i32)> = BTreeSet::new();
+    let mut pen = 0.0f32;
+    let mut last: Option<GlyphId> = None;
+    for c in text.chars() {
+        let glyph_id = scalefont.glyph_id(c);
+        if let Some(last) = last {
+            pen += scalefont.kern(last, glyph_id);
+        }
+        let glyph = glyph_id.with_scale_and_position(scale, point(pen, scalefont.ascent()));
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|x, y, coverage| {
+                if coverage > 0.0 && coverage * 255.0 >= threshold as f32 {
+                    ink.insert((
+                        x as i32 + bounds.min.x.round() as i32,
+                        y as i32 + bounds.min.y.round() as i32,
+                    ));
+                }
+            });
+        }
+        pen += scalefont.h_advance(glyph_id);
+        last = Some(glyph_id);
+    }
+    (ink, pen)
+}
+
+/// Render a string and count the ink pixels in each column and in each row of the line.
+fn render_ink<F: Font>(
+    font: &F,
+    scale: PxScale,
+    text: &str,
+    threshold: u8,
+    line_height: u32,
+) -> RenderedInk {
+    let (ink, advance) = font_ink(font, scale, text, threshold);
+    let mut rows: Histogram = vec![0; line_height as usize];
+    for (_, y) in ink.iter() {
+        if let Some(bin) = usize::try_from(*y).ok().and_then(|y| rows.get_mut(y)) {
+            *bin = bin.saturating_add(1);
+        }
+    }
+    let offset = ink.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let end = ink.iter().map(|(x, _)| *x + 1).max().unwrap_or(0);
+    let mut hist: Histogram = vec![0; (end - offset) as usize];
+    for (x, _) in ink.iter() {
+        let bin = &mut hist[(x - offset) as usize];
+        *bin = bin.saturating_add(1);
+    }
+    RenderedInk {
+        offset,
+        hist,
+        rows,
+        advance,
+    }
+}
+
+/// Create a glyph set by rendering each character of the charset and each of the ligatures in
+/// the options with the font at the provided pixel size, pixels with a coverage of at least
+/// threshold / 255 count towards the histogram.
+///
+/// With side bearings the histograms are padded with the space between the pen position and the
+/// ink on the left, and the remainder of the horizontal advance on the right. Overhanging ink
+/// results in no padding on that side. Characters missing from the font are skipped, whitespace
+/// becomes a zero histogram as wide as its advance. Ligatures are laid out glyph by glyph with
+/// kerning, substitutions from the font are not applied. The line height is the height of the
+/// font, ascent minus descent. Each glyph gets the row profile of its ink within the line.
+pub fn glyph_set_from_font<F: Font>(
+    font: &F,
+    size: f32,
+    charset: &str,
+    threshold: u8,
+    options: &FontGlyphSetOptions,
+) -> GlyphSet {
+    let scale = PxScale::from(size);
+    let scalefont = font.as_scaled(scale);
+
+    let mut glyph_set = GlyphSet {
+        name: options.name.clone(),
+        line_height: scalefont.height().ceil() as u32,
+        ..Default::default()
+    };
+
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+    let entries = charset
+        .chars()
+        .map(|c| c.to_string())
+        .chain(options.ligatures.iter().cloned());
+    for text in entries {
+        if text.is_empty() || !seen.insert(text.clone()) {
+            continue;
+        }
+        if text.chars().any(|c| scalefont.glyph_id(c).0 == 0) {
+            continue;
+        }
+        let rendered = render_ink(font, scale, &text, threshold, glyph_set.line_height);
+        let advance = rendered.advance.round().max(0.0) as usize;
+
+        if rendered.hist.is_empty() {
+            if advance == 0 || !text.chars().all(char::is_whitespace) {
+                continue;
+            }
+            let mut g = Glyph::new(&vec![0; advance], &text);
+            g.set_max_consecutive(Some(1));
+            g.set_trim_left(true);
+            g.set_trim_right(true);
+            g.set_ignore_on_lstrip(true);
+            glyph_set.entries.push(g);
+            continue;
+        }
+
+        let hist = if options.side_bearings {
+            let left = rendered.offset.max(0) as usize;
+            let right = (advance as i32 - rendered.offset - rendered.hist.len() as i32).max(0);
+            let mut padded = vec![0; left];
+            padded.extend(rendered.hist.iter());
+            padded.resize(padded.len() + right as usize, 0);
+            padded
+        } else {
+            rendered.hist
+        };
+        let mut glyph = Glyph::new(&hist, &text);
+        glyph.set_row_profile(Some(rendered.rows));
+        glyph_set.entries.push(glyph);
+    }
+
+    glyph_set.prepare();
+    glyph_set
+}
+
 pub fn draw_histogram(image: &RgbImage, r: &Rect, hist: &Histogram, color: Rgb<u8>) -> RgbImage {
     let mut c = image.clone();
     for x in 0..hist.len() {
@@ -259,6 +432,14 @@ pub fn dev_image_to_glyph_set(
             let sub_img_gray =
                 image::DynamicImage::ImageRgb8(filtered_token.to_image()).into_luma8();
             let sub_img_histogram = image_to_histogram(&sub_img_gray);
+            // The pixels in each row of the line, relative to the top of the line.
+            let row_profile = (0..sub_img_gray.height())
+                .map(|y| {
+                    (0..sub_img_gray.width())
+                        .filter(|x| sub_img_gray.get_pixel(*x, y).0[0] != 0)
+                        .count() as u8
+                })
+                .collect::<Vec<u8>>();
 
             let mut drawable =
                 image_with_rect.sub_image(b.left() as u32, b.top() as u32, b.width(), b.height());
@@ -274,9 +455,9 @@ pub fn dev_image_to_glyph_set(
             let _global_rect =
                 Rect::at(b.left() + z.left(), b.top() + z.top()).of_size(z.width(), z.height());
 
-            result
-                .entries
-                .push(Glyph::new(&sub_img_histogram, &format!("{r}-{c}")));
+            let mut glyph = Glyph::new(&sub_img_histogram, &format!("{r}-{c}"));
+            glyph.set_row_profile(Some(row_profile));
+            result.entries.push(glyph);
         }
     }
     optionally_save_image(&image_with_rect, out_dir, "dev_histogram_boxes.png");
@@ -284,3 +465,80 @@ pub fn dev_image_to_glyph_set(
     result.prepare();
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ab_glyph::FontVec;
+
+    fn test_font() -> FontVec {
+        let path = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+        let data = std::fs::read(path).unwrap_or_else(|e| panic!("font {path} is required: {e}"));
+        FontVec::try_from_vec(data).unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs the DejaVu Sans font in /usr/share/fonts/truetype/dejavu"]
+    fn test_glyph_set_from_font() {
+        let font = test_font();
+        let size = 20.0;
+        let threshold = 128;
+        let options = FontGlyphSetOptions {
+            ligatures: vec!["fi".to_owned()],
+            ..Default::default()
+        };
+        let set = glyph_set_from_font(&font, size, "ab ü\u{10FFFF}", threshold, &options);
+        let scalefont = font.as_scaled(PxScale::from(size));
+        assert_eq!(set.line_height, scalefont.height().ceil() as u32);
+
+        let names: Vec<&str> = set.entries.iter().map(|g| g.glyph()).collect();
+        assert_eq!(names.len(), 5);
+        for name in ["a", "b", " ", "ü", "fi"] {
+            assert!(names.contains(&name), "missing {name}");
+        }
+
+        let find = |name: &str| set.entries.iter().find(|g| g.glyph() == name).unwrap();
+        let space = find(" ");
+        assert!(space.hist().iter().all(|v| *v == 0));
+        let space_advance = scalefont.h_advance(scalefont.glyph_id(' ')).round() as usize;
+        assert_eq!(space.hist().len(), space_advance);
+
+        // Padded glyphs span the advance, the ink matches the rendering by draw_text_mut.
+        let a = find("a");
+        let a_advance = scalefont.h_advance(scalefont.glyph_id('a')).round() as usize;
+        assert_eq!(a.hist().len(), a_advance);
+        // The row profile counts the same ink within the line.
+        let rows = a.row_profile().unwrap();
+        assert_eq!(rows.len(), set.line_height as usize);
+        assert_eq!(rows.iter().map(|v| *v as u32).sum::<u32>(), a.total());
+        assert!(space.row_profile().is_none());
+        let image = render_font_image(
+            (30, 30),
+            &font,
+            size,
+            &[((0, 0), "a".to_owned(), Rgb([255, 255, 255]))],
+        );
+        let mut gray = grayscale(&image);
+        for p in gray.pixels_mut() {
+            p.0[0] = if p.0[0] >= threshold { 255 } else { 0 };
+        }
+        let rendered = image_to_histogram(&gray);
+        let trim = |h: &[u8]| -> Vec<u8> {
+            let start = h.iter().position(|v| *v != 0).unwrap();
+            let end = h.iter().rposition(|v| *v != 0).unwrap();
+            h[start..=end].to_vec()
+        };
+        assert_eq!(trim(a.hist()), trim(&rendered));
+
+        // Without bearings, the histograms start and end with ink.
+        let options = FontGlyphSetOptions {
+            side_bearings: false,
+            ..Default::default()
+        };
+        let set = glyph_set_from_font(&font, size, "ab", threshold, &options);
+        for g in set.entries.iter() {
+            assert_ne!(*g.hist().first().unwrap(), 0);
+            assert_ne!(*g.hist().last().unwrap(), 0);
+        }
+    }
+}
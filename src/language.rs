@@ -0,0 +1,599 @@
+//! Language model guided disambiguation of matches.
+//!
+//! Glyphs with identical histograms can't be told apart by the matcher, and a histogram can
+//! sometimes be split into glyphs in multiple ways. The functionality here uses a language model
+//! to pick the most likely reading among all these interpretations of a match.
+
+use std::collections::{HashMap, HashSet};
+
+use image::{GenericImageView, Pixel, Rgb};
+
+use crate::glyphs::Glyph;
+use crate::{
+    ambiguous_tokens, ColorLabel, Confidence, HistogramType, LabelledGlyph, Match2D, Matcher, Rect,
+};
+
+/// Trait for models that score how likely a piece of text is.
+pub trait LanguageModel {
+    /// Score the provided text, higher is more likely.
+    fn score(&self, text: &str) -> f32;
+
+    /// Score text that may be cut off in the middle of a word, used to compare the partial readings
+    /// during the search. Defaults to the score of the text.
+    fn prefix_score(&self, text: &str) -> f32 {
+        self.score(text)
+    }
+}
+
+/// Language model based on a known vocabulary of phrases, like item names.
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+    /// The full phrases.
+    phrases: HashSet<String>,
+    /// The individual words that make up the phrases.
+    words: HashSet<String>,
+    /// All prefixes of the words, including the words themselves.
+    word_prefixes: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Create a dictionary from the provided phrases, each phrase may contain multiple words.
+    pub fn new<S: AsRef<str>>(phrases: &[S]) -> Self {
+        let mut dictionary: Dictionary = Default::default();
+        for phrase in phrases.iter() {
+            dictionary.insert(phrase.as_ref());
+        }
+        dictionary
+    }
+
+    /// Add a phrase to the dictionary.
+    pub fn insert(&mut self, phrase: &str) {
+        let phrase = phrase.trim();
+        for word in phrase.split_whitespace() {
+            for (i, c) in word.char_indices() {
+                self.word_prefixes
+                    .insert(word[..i + c.len_utf8()].to_owned());
+            }
+            self.words.insert(word.to_owned());
+        }
+        self.phrases.insert(phrase.to_owned());
+    }
+}
+
+impl LanguageModel for Dictionary {
+    /// The score is the number of characters in known words, doubled if the text is a known phrase.
+    fn score(&self, text: &str) -> f32 {
+        let known_chars = text
+            .split_whitespace()
+            .filter(|w| self.words.contains(*w))
+            .map(|w| w.chars().count())
+            .sum::<usize>();
+        if self.phrases.contains(text.trim()) {
+            (2 * known_chars) as f32
+        } else {
+            known_chars as f32
+        }
+    }
+
+    /// Like the score, but the characters of a last word that is the start of a known word count.
+    fn prefix_score(&self, text: &str) -> f32 {
+        let score = self.score(text);
+        if text.ends_with(char::is_whitespace) {
+            return score;
+        }
+        match text.split_whitespace().next_back() {
+            Some(last) if !self.words.contains(last) && self.word_prefixes.contains(last) => {
+                score + last.chars().count() as f32
+            }
+            _ => score,
+        }
+    }
+}
+
+/// Character n-gram language model with add-one smoothing.
+#[derive(Clone, Debug, Default)]
+pub struct CharNgramModel {
+    /// The order of the model, the number of characters in each n-gram.
+    n: usize,
+    /// Counts of each n-gram.
+    counts: HashMap<String, u32>,
+    /// Counts of each context, the n-gram without its last character.
+    context_counts: HashMap<String, u32>,
+    /// Number of distinct characters seen.
+    vocabulary: usize,
+}
+
+/// Character used to pad the start of text, such that the first characters also have a context.
+const NGRAM_PADDING: char = '\u{2}';
+
+impl CharNgramModel {
+    /// Train a model of order n on the provided example texts.
+    pub fn train<S: AsRef<str>>(corpus: &[S], n: usize) -> Self {
+        let n = std::cmp::max(n, 1);
+        let mut model = CharNgramModel {
+            n,
+            ..Default::default()
+        };
+        let mut vocabulary: HashSet<char> = Default::default();
+        for text in corpus.iter() {
+            vocabulary.extend(text.as_ref().chars());
+            for_each_ngram(n, text.as_ref(), |context, ngram| {
+                *model.context_counts.entry(context.to_owned()).or_default() += 1;
+                *model.counts.entry(ngram.to_owned()).or_default() += 1;
+            });
+        }
+        model.vocabulary = vocabulary.len();
+        model
+    }
+}
+
+/// Call the function with the context and the n-gram of order n for each character in the text.
+fn for_each_ngram<F: FnMut(&str, &str)>(n: usize, text: &str, mut f: F) {
+    let padded = std::iter::repeat_n(NGRAM_PADDING, n - 1)
+        .chain(text.chars())
+        .collect::<Vec<char>>();
+    for window in padded.windows(n) {
+        let ngram = window.iter().collect::<String>();
+        let context = window[..n - 1].iter().collect::<String>();
+        f(&context, &ngram);
+    }
+}
+
+impl LanguageModel for CharNgramModel {
+    /// The score is the log probability of the text.
+    fn score(&self, text: &str) -> f32 {
+        let mut log_probability = 0.0f32;
+        for_each_ngram(self.n, text, |context, ngram| {
+            let count = self.counts.get(ngram).copied().unwrap_or(0);
+            let context_count = self.context_counts.get(context).copied().unwrap_or(0);
+            let p = (count + 1) as f32 / (context_count as usize + self.vocabulary + 1) as f32;
+            log_probability += p.ln();
+        });
+        log_probability
+    }
+}
+
+/// A partial interpretation of the histogram during the search.
+#[derive(Clone, Debug)]
+struct Hypothesis<'a> {
+    /// The glyphs with the start and end of the bins they cover in the histogram.
+    glyphs: Vec<(&'a Glyph, usize, usize)>,
+    text: String,
+}
+
+/// Count the pixels of the label of the match in each column of its location, like the histogram
+/// of the window it was found in. Only the columns in which the glyphs of the match hold pixels
+/// are counted, the pixels that the match left unexplained are left out for the other readings as
+/// well.
+fn match_columns<I: GenericImageView>(
+    m: &Match2D,
+    image: &I,
+    labels: &[ColorLabel],
+) -> Vec<HistogramType>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    let location = &m.location;
+    let mut histogram: Vec<HistogramType> = vec![0; location.w as usize + 1];
+    let label = m
+        .tokens
+        .first()
+        .and_then(|t| labels.iter().position(|l| l.1 == t.label));
+    let Some(label) = label else {
+        return histogram;
+    };
+    let last_row = std::cmp::min(location.y + location.h, image.height().saturating_sub(1));
+    for t in m.tokens.iter() {
+        for (i, v) in t.glyph.hist().iter().enumerate() {
+            let x = t.location.x + i as u32;
+            if *v == 0 || x < location.left() || x > location.right() || x >= image.width() {
+                continue;
+            }
+            // The pixel belongs to the first label whose rule matches it, like in the scan.
+            histogram[(x - location.x) as usize] = (location.y..=last_row)
+                .filter(|y| {
+                    let c = image.get_pixel(x, *y);
+                    let c = c.channels();
+                    let p = Rgb([c[0], c[1], c[2]]);
+                    labels.iter().position(|l| l.0.matches(p)) == Some(label)
+                })
+                .count() as HistogramType;
+        }
+    }
+    histogram
+}
+
+/// Search all ways in which the histogram of the match can be split into glyphs, returns the
+/// readings that cover every non-zero bin with their score, best first. Only the first of the
+/// readings with the same text is kept. The histogram holds the columns of the location of the
+/// match.
+fn readings<'a>(
+    m: &Match2D<'a>,
+    histogram: &[HistogramType],
+    matcher: &'a (impl Matcher + ?Sized),
+    model: &dyn LanguageModel,
+    beam_width: usize,
+) -> Vec<(f32, Hypothesis<'a>)> {
+    if m.tokens.is_empty() || histogram.iter().all(|v| *v == 0) {
+        return vec![];
+    }
+    let n = histogram.len();
+    let first_non_zero = histogram.iter().position(|v| *v != 0).unwrap_or(n);
+    let last_non_zero = histogram.iter().rposition(|v| *v != 0).unwrap_or(0);
+
+    fn extend<'a>(
+        beams: &mut [Vec<Hypothesis<'a>>],
+        hypothesis: &Hypothesis<'a>,
+        glyph: &'a Glyph,
+        start: usize,
+        end: usize,
+    ) {
+        let mut new_hypothesis = hypothesis.clone();
+        new_hypothesis.glyphs.push((glyph, start, end));
+        new_hypothesis.text.push_str(glyph.glyph());
+        beams[end].push(new_hypothesis);
+    }
+
+    let mut beams: Vec<Vec<Hypothesis<'a>>> = vec![vec![]; n + 1];
+    let start = Hypothesis {
+        glyphs: vec![],
+        text: String::new(),
+    };
+
+    // The first glyph may have been matched without its left side bearing.
+    if first_non_zero < n {
+        for glyph in matcher.lstrip_find_all_matches(&histogram[first_non_zero..]) {
+            let width = glyph
+                .lstrip_hist()
+                .expect("must have had a lstrip histogram to find it")
+                .len();
+            extend(
+                &mut beams,
+                &start,
+                glyph,
+                first_non_zero,
+                first_non_zero + width,
+            );
+        }
+    }
+    beams[0].push(start);
+
+    for pos in 0..n {
+        let mut hypotheses = std::mem::take(&mut beams[pos]);
+        if hypotheses.is_empty() {
+            continue;
+        }
+        // Prune to the best hypotheses for this position, these may end halfway through a word.
+        let mut scored = hypotheses
+            .drain(..)
+            .map(|h| (model.prefix_score(&h.text), h))
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(std::cmp::max(beam_width, 1));
+        hypotheses.extend(scored.into_iter().map(|(_, h)| h));
+
+        let glyphs = matcher.find_all_matches(&histogram[pos..]);
+        for hypothesis in hypotheses.iter() {
+            for glyph in glyphs.iter() {
+                extend(&mut beams, hypothesis, glyph, pos, pos + glyph.hist().len());
+            }
+        }
+        beams[pos] = hypotheses;
+    }
+
+    // All readings that cover every non-zero bin are candidates.
+    let mut res: Vec<(f32, Hypothesis<'a>)> = vec![];
+    for hypothesis in beams.drain(..).skip(last_non_zero + 1).flatten() {
+        if res.iter().any(|(_, h)| h.text == hypothesis.text) {
+            continue;
+        }
+        res.push((model.score(&hypothesis.text), hypothesis));
+    }
+    // Stable, such that the first found reading wins among equal scores.
+    res.sort_by(|a, b| b.0.total_cmp(&a.0));
+    res
+}
+
+/// Create the match for a reading of the provided match.
+fn reading_to_match<'a>(
+    m: &Match2D<'a>,
+    hypothesis: &Hypothesis<'a>,
+    matcher: &'a (impl Matcher + ?Sized),
+) -> Match2D<'a> {
+    // The histogram starts at the location of the match, the tokens span their whole glyph like
+    // the tokens of the scan, also the first one that may have been matched without its left side
+    // bearing.
+    let base = m.location.x as i64;
+    let tokens = hypothesis
+        .glyphs
+        .iter()
+        .map(|(glyph, _, end)| LabelledGlyph {
+            glyph,
+            label: m.tokens[0].label,
+            location: Rect {
+                x: (base + *end as i64 - glyph.hist().len() as i64).max(0) as u32,
+                y: m.bounds.y,
+                w: glyph.hist().len() as u32 - 1,
+                h: m.bounds.h,
+            },
+        })
+        .collect::<Vec<_>>();
+    Match2D {
+        confidence: Confidence {
+            ambiguous_tokens: ambiguous_tokens(&tokens, matcher),
+            ..m.confidence
+        },
+        tokens,
+        location: m.location.clone(),
+        bounds: m.bounds.clone(),
+    }
+}
+
+/// Find the most likely reading of a match according to the language model.
+///
+/// The histogram of the match is read again from the columns of its location in the image, with
+/// the labels it was scanned with. Then all ways in which this histogram can be split into glyphs
+/// are searched, including glyphs with identical histograms. To keep this tractable only the
+/// beam_width partial readings with the best prefix score are kept for each position in the
+/// histogram. The original match is returned if no reading scores better. The location of the
+/// match must be in the image, matches of oriented scans are not supported.
+pub fn disambiguate<'a, I: GenericImageView>(
+    m: &Match2D<'a>,
+    image: &I,
+    labels: &[ColorLabel],
+    matcher: &'a (impl Matcher + ?Sized),
+    model: &dyn LanguageModel,
+    beam_width: usize,
+) -> Match2D<'a>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    let original = m.to_string();
+    let histogram = match_columns(m, image, labels);
+    match readings(m, &histogram, matcher, model, beam_width).first() {
+        Some((score, hypothesis))
+            if hypothesis.text != original && *score > model.score(&original) =>
+        {
+            reading_to_match(m, hypothesis, matcher)
+        }
+        _ => m.clone(),
+    }
+}
+
+/// Find the n most likely readings of a match according to the language model, best first.
+///
+/// The readings are searched like [`disambiguate`] does, each distinct text is returned once. The
+/// original reading is part of the result if it is among the n best. The scan functions only
+/// produce the original reading, this is called on their matches with the matcher that was used.
+pub fn n_best<'a, I: GenericImageView>(
+    m: &Match2D<'a>,
+    image: &I,
+    labels: &[ColorLabel],
+    matcher: &'a (impl Matcher + ?Sized),
+    model: &dyn LanguageModel,
+    beam_width: usize,
+    n: usize,
+) -> Vec<Match2D<'a>>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    let original = m.to_string();
+    let histogram = match_columns(m, image, labels);
+    readings(m, &histogram, matcher, model, beam_width)
+        .iter()
+        .take(n)
+        .map(|(_, hypothesis)| {
+            if hypothesis.text == original {
+                m.clone()
+            } else {
+                reading_to_match(m, hypothesis, matcher)
+            }
+        })
+        .collect()
+}
+
+/// Apply disambiguate to all matches found in the image.
+pub fn disambiguate_matches<'a, I: GenericImageView>(
+    matches: &[Match2D<'a>],
+    image: &I,
+    labels: &[ColorLabel],
+    matcher: &'a (impl Matcher + ?Sized),
+    model: &dyn LanguageModel,
+    beam_width: usize,
+) -> Vec<Match2D<'a>>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    matches
+        .iter()
+        .map(|m| disambiguate(m, image, labels, matcher, model, beam_width))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyphs::GlyphSet;
+    use crate::matcher::{LongestGlyphMatcher, Tolerance};
+    use crate::{bin_glyph_matcher, match_resolver, LabelledHistogram};
+    use image::RgbImage;
+
+    const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+
+    fn glyph_set() -> GlyphSet {
+        let mut glyph_set: GlyphSet = Default::default();
+        let entries: [(&[u8], &str); 8] = [
+            (&[0, 7, 0], "l"),
+            (&[0, 7, 0], "I"),
+            (&[0, 3, 2, 0], "r"),
+            (&[0, 3, 2, 3, 0], "o"),
+            (&[0, 5, 1, 4, 0], "n"),
+            (&[0, 7, 2, 3, 0], "b"),
+            (&[0, 2, 3, 4, 0], "a"),
+            (&[0, 3, 2, 0, 0, 5, 1, 4, 0], "m"),
+        ];
+        for (hist, glyph) in entries.iter() {
+            glyph_set.entries.push(Glyph::new(hist, glyph));
+        }
+        glyph_set.line_height = 7;
+        glyph_set.prepare();
+        glyph_set
+    }
+
+    fn labels() -> Vec<ColorLabel> {
+        vec![(WHITE.into(), 0)]
+    }
+
+    /// Match the histogram, returns the single match with an image that holds the histogram.
+    fn read_histogram<'a>(
+        histogram: &[u8],
+        line_height: u32,
+        matcher: &'a LongestGlyphMatcher,
+    ) -> (Match2D<'a>, RgbImage) {
+        let mut image = RgbImage::new(histogram.len() as u32, line_height);
+        for (x, v) in histogram.iter().enumerate() {
+            for y in 0..*v as u32 {
+                image.put_pixel(x as u32, y, WHITE);
+            }
+        }
+        let labelled = LabelledHistogram::from_u8(histogram, labels()[0].clone());
+        let matches = bin_glyph_matcher(&labelled, matcher);
+        let mut matches_2d = match_resolver(0, line_height, &matches);
+        assert_eq!(matches_2d.len(), 1);
+        (matches_2d.remove(0), image)
+    }
+
+    fn read<'a>(
+        glyph_set: &GlyphSet,
+        matcher: &'a LongestGlyphMatcher,
+        text: &str,
+    ) -> (Match2D<'a>, RgbImage) {
+        let mut histogram: Vec<u8> = vec![0, 0, 0];
+        for c in text.chars() {
+            let glyph = glyph_set
+                .entries
+                .iter()
+                .find(|g| g.glyph() == c.to_string())
+                .unwrap();
+            histogram.extend(glyph.hist());
+        }
+        histogram.extend([0, 0, 0]);
+        read_histogram(&histogram, glyph_set.line_height, matcher)
+    }
+
+    #[test]
+    fn test_identical_histograms() {
+        let glyph_set = glyph_set();
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+
+        // Without context, l is picked because it is first.
+        let (m, image) = read(&glyph_set, &matcher, "Iron");
+        assert_eq!(m.to_string(), "lron");
+
+        let dictionary = Dictionary::new(&["Iron Bar", "Iron"]);
+        let res = disambiguate(&m, &image, &labels(), &matcher, &dictionary, 8);
+        assert_eq!(res.to_string(), "Iron");
+        assert_eq!(res.location, m.location);
+
+        let model = CharNgramModel::train(&["Iron", "Iron ore", "Ironclad"], 3);
+        let res = disambiguate(&m, &image, &labels(), &matcher, &model, 8);
+        assert_eq!(res.to_string(), "Iron");
+    }
+
+    #[test]
+    fn test_n_best() {
+        let glyph_set = glyph_set();
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        let (m, image) = read(&glyph_set, &matcher, "Iron");
+
+        let dictionary = Dictionary::new(&["Iron"]);
+        let res = n_best(&m, &image, &labels(), &matcher, &dictionary, 8, 2);
+        let texts = res.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["Iron", "lron"]);
+        // The original reading is returned as is.
+        assert_eq!(res[1], m);
+        // Both readings hold an ambiguous glyph and cover the same pixels.
+        assert_eq!(res[0].confidence.ambiguous_tokens, 1);
+        assert_eq!(
+            res[0].confidence.matched_pixels,
+            m.confidence.matched_pixels
+        );
+        assert_eq!(res[0].tokens[1..], m.tokens[1..]);
+
+        // Every distinct reading is returned once.
+        let res = n_best(&m, &image, &labels(), &matcher, &dictionary, 8, 10);
+        assert_eq!(res.len(), 2);
+    }
+
+    #[test]
+    fn test_competing_segmentations() {
+        let glyph_set = glyph_set();
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+
+        // The greedy matcher takes the longest glyph, m.
+        let (m, image) = read(&glyph_set, &matcher, "barn");
+        assert_eq!(m.to_string(), "bam");
+
+        let dictionary = Dictionary::new(&["barn"]);
+        let res = disambiguate(&m, &image, &labels(), &matcher, &dictionary, 8);
+        assert_eq!(res.to_string(), "barn");
+        // The r and n together cover the columns of the m.
+        assert_eq!(res.tokens[1], m.tokens[1]);
+        let (r, n) = (&res.tokens[2].location, &res.tokens[3].location);
+        let m_location = &m.tokens[2].location;
+        assert_eq!(r.x, m_location.x);
+        assert_eq!(n.x + n.w, m_location.x + m_location.w);
+        assert!(r.x + r.w < n.x);
+
+        // Without evidence for something else, the original is kept.
+        let dictionary = Dictionary::new(&["bam"]);
+        let res = disambiguate(&m, &image, &labels(), &matcher, &dictionary, 8);
+        assert_eq!(res.to_string(), "bam");
+    }
+
+    #[test]
+    fn test_readings_use_the_image() {
+        let mut glyph_set: GlyphSet = Default::default();
+        glyph_set.entries.push(Glyph::new(&[0, 3, 0], "i"));
+        glyph_set.entries.push(Glyph::new(&[0, 2, 0], "j"));
+        glyph_set.line_height = 7;
+        glyph_set.prepare();
+        let mut matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        matcher.set_tolerance(Some(Tolerance {
+            per_bin: 1,
+            total: 1,
+        }));
+
+        // The column holds 4 pixels, i is within the tolerance, j is not.
+        let (m, image) = read_histogram(&[0, 0, 0, 4, 0, 0, 0], 7, &matcher);
+        assert_eq!(m.to_string(), "i");
+
+        // The histogram of i is within the tolerance of j, but the column in the image isn't.
+        let dictionary = Dictionary::new(&["j"]);
+        let res = disambiguate(&m, &image, &labels(), &matcher, &dictionary, 8);
+        assert_eq!(res.to_string(), "i");
+        let res = n_best(&m, &image, &labels(), &matcher, &dictionary, 8, 10);
+        assert_eq!(res, vec![m.clone()]);
+
+        // Pixels of other colors don't count.
+        let other = vec![(Rgb([255u8, 0, 0]).into(), 1)];
+        assert!(n_best(&m, &image, &other, &matcher, &dictionary, 8, 10).is_empty());
+    }
+
+    #[test]
+    fn test_more_ambiguities_than_beam_width() {
+        let glyph_set = glyph_set();
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        let (m, image) = read(&glyph_set, &matcher, "IlIlI");
+        assert_eq!(m.to_string(), "lllll");
+
+        // Each of the five positions can be an l or an I, the partial readings that start the word
+        // must be kept over the others while the beam only holds two of them.
+        let dictionary = Dictionary::new(&["IlIlI"]);
+        let res = disambiguate(&m, &image, &labels(), &matcher, &dictionary, 2);
+        assert_eq!(res.to_string(), "IlIlI");
+        assert_eq!(dictionary.prefix_score("IlI"), 3.0);
+        assert_eq!(dictionary.prefix_score("IlI "), 0.0);
+        assert_eq!(dictionary.prefix_score("lIl"), 0.0);
+    }
+}
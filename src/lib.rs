@@ -8,8 +8,13 @@
 // https://releases.llvm.org/11.0.1/docs/Benchmarking.html
 // https://bheisler.github.io/criterion.rs/book/user_guide/command_line_options.html#baselines
 
+pub mod error;
+pub use error::{Error, Result};
+
 pub mod glyphs;
 
+pub mod binary;
+
 // mod interface;
 // pub use interface::*;
 
@@ -18,6 +23,23 @@ use image::{GenericImageView, Pixel, Rgb};
 
 pub mod matcher;
 
+pub mod language;
+
+pub mod color;
+pub use color::{ColorClassifier, ColorRule};
+
+pub mod frame;
+
+pub mod orientation;
+pub use orientation::Orientation;
+
+pub mod scanner;
+pub use scanner::{OverlapPolicy, ScanOptions, Scanner};
+
+pub mod training;
+
+pub mod evaluation;
+
 pub mod util;
 
 /// Type to hold a simple 1D histogram.
@@ -28,7 +50,9 @@ use serde::{Deserialize, Serialize};
 // This here ensures that we have image support when the feature is enabled, but also for all tests.
 pub mod image_support;
 
-#[cfg(test)]
+// Test helpers and the synthetic image generator, also available to benchmarks with the testing
+// feature.
+#[cfg(any(test, feature = "testing"))]
 pub mod test_util;
 
 /*
@@ -80,7 +104,7 @@ pub fn histogram_glyph_matcher(
     let mut i: usize = 0;
     let mut res: Vec<(glyphs::Glyph, u8)> = Vec::new();
 
-    while i < v.len() - 1 {
+    while i + 1 < v.len() {
         if v[i] == 0 {
             i += 1;
             continue;
@@ -129,14 +153,16 @@ pub fn histogram_glyph_matcher(
 /// The data type used in histograms, this is also the maximum height of the window.
 pub type HistogramType = u32; // Do not make this u16, that's much slower.
 
-/// Relate a particular color to a label.
-pub type ColorLabel = (Rgb<u8>, u32);
+/// Relate a color rule to a label, a bare color can be turned into an exact rule with `into()`.
+pub type ColorLabel = (ColorRule, u32);
 
 /// A glyph with an associated label.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub struct LabelledGlyph<'a> {
     pub glyph: &'a glyphs::Glyph,
     pub label: u32,
+    /// The columns covered by this glyph, vertically it spans the bounds of the match.
+    pub location: Rect,
 }
 
 /// A 2D match is a sequence of consecutive glyphs.
@@ -144,11 +170,86 @@ pub struct LabelledGlyph<'a> {
 pub struct Match2D<'a> {
     /// The glyphs that make up this match.
     pub tokens: Vec<LabelledGlyph<'a>>,
-    /// The location that is covered by this match.
+    /// The location that is covered by this match, vertically this is the window it was found in.
     pub location: Rect,
+    /// The location shrunk vertically to the rows that hold pixels of the match.
+    pub bounds: Rect,
+    /// How well the glyphs explain the pixels in the location.
+    pub confidence: Confidence,
+}
+
+/// Measures of how well a match explains the pixels in its location.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct Confidence {
+    /// Number of pixels covered by the matched glyphs.
+    pub matched_pixels: u32,
+    /// Number of pixels of the label that are present in the location of the match.
+    pub region_pixels: u32,
+    /// Number of columns in the location that hold pixels of the label, but aren't covered by any of
+    /// the glyphs.
+    pub unmatched_columns: u32,
+    /// Number of tokens for which the matcher has other glyphs with an identical histogram, these
+    /// can't be told apart without context.
+    pub ambiguous_tokens: u32,
+}
+
+impl Confidence {
+    /// Fraction of the pixels in the location that is covered by the glyphs, between 0 and 1.
+    pub fn coverage(&self) -> f32 {
+        if self.region_pixels == 0 {
+            return 0.0;
+        }
+        self.matched_pixels.min(self.region_pixels) as f32 / self.region_pixels as f32
+    }
+}
+
+/// Count the columns of the match that hold pixels in the histogram, but aren't covered by a token.
+fn unmatched_columns(m: &Match2D, histogram: &[HistogramType]) -> u32 {
+    (m.location.left()..=m.location.right())
+        .filter(|x| {
+            histogram[*x as usize] != 0
+                && !m
+                    .tokens
+                    .iter()
+                    .any(|t| t.location.left() <= *x && *x <= t.location.right())
+        })
+        .count() as u32
+}
+
+/// Count the tokens for which the matcher also knows other glyphs with an identical histogram.
+pub(crate) fn ambiguous_tokens(tokens: &[LabelledGlyph], matcher: &(impl Matcher + ?Sized)) -> u32 {
+    tokens
+        .iter()
+        .filter(|t| matcher.is_ambiguous(t.glyph))
+        .count() as u32
 }
 
 impl<'a> Match2D<'a> {
+    /// Replace the location, the bounds and the locations of the tokens by the rectangles they
+    /// map to.
+    pub(crate) fn map_rects(&mut self, f: impl Fn(&Rect) -> Rect) {
+        self.location = f(&self.location);
+        self.bounds = f(&self.bounds);
+        for t in self.tokens.iter_mut() {
+            t.location = f(&t.location);
+        }
+    }
+
+    /// Shrink the bounds and the tokens vertically to the rows with pixels, given the number of
+    /// pixels in each row of the location. Nothing changes if there are no pixels.
+    fn tighten(&mut self, rows: &[HistogramType]) {
+        let first = rows.iter().position(|v| *v != 0);
+        let last = rows.iter().rposition(|v| *v != 0);
+        if let (Some(first), Some(last)) = (first, last) {
+            self.bounds.y = self.location.y + first as u32;
+            self.bounds.h = (last - first) as u32;
+            for t in self.tokens.iter_mut() {
+                t.location.y = self.bounds.y;
+                t.location.h = self.bounds.h;
+            }
+        }
+    }
+
     /// Concatenate all tokens into a string.
     pub fn to_string(&self) -> String {
         self.tokens
@@ -179,10 +280,53 @@ pub struct Match<'a> {
     pub width: u32,
 }
 
+/// How the glyphs in a histogram are found when scanning.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Decoder {
+    /// Take the longest glyph at each position, see [`bin_glyph_matcher`].
+    #[default]
+    Greedy,
+    /// Search for the interpretation that covers the most pixels, see [`search_glyph_matcher`].
+    /// This finds text that the greedy decoder misses when a long glyph is a prefix of the
+    /// correct reading, at the cost of trying all matching glyphs at every position.
+    Search,
+}
+
 /// Trait that provides glyph matching functionality.
 pub trait Matcher {
     fn find_match(&self, histogram: &[HistogramType]) -> Option<&glyphs::Glyph>;
     fn lstrip_find_match(&self, histogram: &[HistogramType]) -> Option<&glyphs::Glyph>;
+
+    /// Return all glyphs that match the start of the histogram, not just the best one. Matchers
+    /// that allow deviations in find_match allow the same deviations here.
+    fn find_all_matches(&self, histogram: &[HistogramType]) -> Vec<&glyphs::Glyph> {
+        self.find_match(histogram).into_iter().collect()
+    }
+
+    /// Return all glyphs that match the start of the histogram using their lstripped histogram.
+    fn lstrip_find_all_matches(&self, histogram: &[HistogramType]) -> Vec<&glyphs::Glyph> {
+        self.lstrip_find_match(histogram).into_iter().collect()
+    }
+
+    /// Whether other glyphs have a histogram identical to this glyph, such that they can't be told
+    /// apart without context.
+    fn is_ambiguous(&self, glyph: &glyphs::Glyph) -> bool {
+        let hist = glyph
+            .hist()
+            .iter()
+            .map(|v| *v as HistogramType)
+            .collect::<Vec<_>>();
+        self.find_all_matches(&hist)
+            .iter()
+            .filter(|g| g.hist() == glyph.hist())
+            .count()
+            > 1
+    }
+
+    /// The decoder that is used to find the glyphs in the histograms when scanning.
+    fn decoder(&self) -> Decoder {
+        Decoder::Greedy
+    }
 }
 
 // There are situation where linear - longest glyph matching is not correct;
@@ -198,6 +342,7 @@ pub trait Matcher {
 //
 // This requires the glyph matcher to return a list of all possible glyphs for for the given
 // histogram. Then we can perform a search over the possible interpretations in the histogram.
+// The search_glyph_matcher function does this, using Matcher::find_all_matches.
 //
 // This would also allow for accounting for situations where multiple glyphs map to identical
 // histograms and we need context to decide which one would be best.
@@ -228,7 +373,7 @@ pub fn bin_glyph_matcher<'a>(
     // to compare.
     let mut use_stripped = true;
 
-    while i < histogram.len() - 1 {
+    while i + 1 < histogram.len() {
         // If we are using stripped symbols, remove the padding from the left, this will be very fast.
         if use_stripped {
             if histogram[i] == 0 {
@@ -295,12 +440,189 @@ pub fn bin_glyph_matcher<'a>(
     res
 }
 
-/// Return a string as best matched from the provided histogram.
+/// Step taken in the search performed by search_glyph_matcher.
+#[derive(Debug, Copy, Clone)]
+enum SearchStep<'a> {
+    /// The bin was not matched by any glyph.
+    Skip,
+    /// A glyph was matched, stripped denotes whether the lstripped histogram was used.
+    Glyph {
+        glyph: &'a glyphs::Glyph,
+        stripped: bool,
+    },
+}
+
+/// This function performs the same task as bin_glyph_matcher, but instead of greedily taking the
+/// longest glyph it searches over all glyphs returned by the matcher to find the interpretation
+/// that covers the most pixels in the histogram. If multiple interpretations cover the same number
+/// of pixels, the one that covers the most bins with glyphs is used.
+pub fn search_glyph_matcher<'a>(
+    labelled_histogram: &LabelledHistogram,
+    matcher: &'a (impl Matcher + ?Sized),
+) -> Vec<Match<'a>> {
+    let histogram = &labelled_histogram.histogram;
+    let n = histogram.len();
+
+    // State is the position in the histogram and whether the previous step matched a glyph, only
+    // directly after a glyph the full histograms (including left side bearing) are matched,
+    // otherwise the lstripped histograms are used, like in bin_glyph_matcher.
+    // The score for each state is (covered pixels, covered bins), with the step that led to it.
+    type Score = (u32, u32);
+    type Previous<'a> = (usize, usize, SearchStep<'a>);
+    let mut scores: Vec<[Option<Score>; 2]> = vec![[None, None]; n + 1];
+    let mut previous: Vec<[Option<Previous<'a>>; 2]> = vec![[None, None]; n + 1];
+    scores[0][0] = Some((0, 0));
+
+    fn update<'a>(
+        scores: &mut [[Option<Score>; 2]],
+        previous: &mut [[Option<Previous<'a>>; 2]],
+        to: (usize, usize),
+        score: Score,
+        from: Previous<'a>,
+    ) {
+        let current = &mut scores[to.0][to.1];
+        if current.map(|c| score > c).unwrap_or(true) {
+            *current = Some(score);
+            previous[to.0][to.1] = Some(from);
+        }
+    }
+
+    for i in 0..n {
+        for after_glyph in [0usize, 1usize] {
+            let score = if let Some(score) = scores[i][after_glyph] {
+                score
+            } else {
+                continue;
+            };
+            let remainder = &histogram[i..];
+
+            // Skipping a bin is always possible.
+            update(
+                &mut scores,
+                &mut previous,
+                (i + 1, 0),
+                score,
+                (i, after_glyph, SearchStep::Skip),
+            );
+
+            // Directly after a glyph, match the full histograms.
+            if after_glyph == 1 {
+                for glyph in matcher.find_all_matches(remainder) {
+                    let width = glyph.hist().len();
+                    update(
+                        &mut scores,
+                        &mut previous,
+                        (i + width, 1),
+                        (score.0 + glyph.total(), score.1 + width as u32),
+                        (
+                            i,
+                            after_glyph,
+                            SearchStep::Glyph {
+                                glyph,
+                                stripped: false,
+                            },
+                        ),
+                    );
+                }
+            }
+
+            // On non-zero bins, glyphs can always start by means of their stripped histogram.
+            if histogram[i] != 0 {
+                for glyph in matcher.lstrip_find_all_matches(remainder) {
+                    let width = glyph
+                        .lstrip_hist()
+                        .expect("must have had a lstrip histogram to find it")
+                        .len();
+                    update(
+                        &mut scores,
+                        &mut previous,
+                        (i + width, 1),
+                        (score.0 + glyph.total(), score.1 + width as u32),
+                        (
+                            i,
+                            after_glyph,
+                            SearchStep::Glyph {
+                                glyph,
+                                stripped: true,
+                            },
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    // Find the best final state and walk back to obtain the steps taken.
+    let mut state = if scores[n][1] > scores[n][0] {
+        (n, 1)
+    } else {
+        (n, 0)
+    };
+    let mut steps: Vec<(usize, usize, SearchStep<'a>)> = vec![];
+    while let Some((i, after_glyph, step)) = previous[state.0][state.1] {
+        steps.push((i, after_glyph, step));
+        state = (i, after_glyph);
+    }
+    steps.reverse();
+
+    // Convert the steps into matches, runs of skipped zero bins become whitespace. Like in
+    // bin_glyph_matcher, the bin directly after a glyph is skipped without starting whitespace,
+    // and the width of whitespace is one less than the number of bins it covers.
+    let mut res: Vec<Match<'a>> = Vec::with_capacity(32);
+    let mut whitespace_start: Option<usize> = None;
+    for (i, after_glyph, step) in steps {
+        let is_skip = matches!(step, SearchStep::Skip);
+        if is_skip && after_glyph == 0 && histogram[i] == 0 {
+            whitespace_start.get_or_insert(i);
+            continue;
+        }
+        if let Some(start) = whitespace_start.take() {
+            res.push(Match {
+                token: Token::WhiteSpace(i - start - 1),
+                position: start as u32,
+                width: (i - start - 1) as u32,
+            });
+        }
+        if let SearchStep::Glyph { glyph, stripped } = step {
+            let offset = if stripped { glyph.first_non_zero() } else { 0 };
+            res.push(Match {
+                token: Token::Glyph {
+                    glyph,
+                    label: labelled_histogram.label.1,
+                },
+                position: (i as u32).saturating_sub(offset as u32),
+                width: glyph.hist().len() as u32,
+            });
+        }
+    }
+    if let Some(start) = whitespace_start {
+        res.push(Match {
+            token: Token::WhiteSpace(n - start - 1),
+            position: start as u32,
+            width: (n - start - 1) as u32,
+        });
+    }
+    res
+}
+
+/// Find the glyphs in the histogram with the decoder of the matcher.
+fn decode_histogram<'a>(
+    labelled_histogram: &LabelledHistogram,
+    matcher: &'a dyn Matcher,
+) -> Vec<Match<'a>> {
+    match matcher.decoder() {
+        Decoder::Greedy => bin_glyph_matcher(labelled_histogram, matcher),
+        Decoder::Search => search_glyph_matcher(labelled_histogram, matcher),
+    }
+}
+
+/// Return a string as best matched from the provided histogram, using the decoder of the matcher.
 ///
 /// This is the function to use when matching a single line to a single string.
 pub fn match_histogram_to_string(histogram: &[u8], matcher: &dyn Matcher) -> String {
-    let labelled_histogram = LabelledHistogram::from_u8(histogram, (Rgb([255, 255, 255]), 0));
-    let matches = bin_glyph_matcher(&labelled_histogram, matcher);
+    let labelled_histogram =
+        LabelledHistogram::from_u8(histogram, (Rgb([255, 255, 255]).into(), 0));
+    let matches = decode_histogram(&labelled_histogram, matcher);
     let mut s = String::new();
     for m in matches {
         match m.token {
@@ -373,6 +695,7 @@ impl Rect {
     }
 }
 
+use std::borrow::Cow;
 use std::collections::VecDeque;
 
 /// Helper to accept  matches if they have moved out of the window.
@@ -390,11 +713,7 @@ fn finalize_considerations<'a>(
 fn decide_on_matches<'a>(matches: Vec<Match2D<'a>>, res_consider: &mut VecDeque<Match2D<'a>>) {
     for current_match in matches {
         // Determine the number of pixels this glyph sequence matched.
-        let current_matching = current_match
-            .tokens
-            .iter()
-            .map(|z| z.glyph.total())
-            .fold(0, |x, a| x + a);
+        let current_matching = current_match.confidence.matched_pixels;
         // Now, we need to decide whether this block of glyphs is better than the ones currently
         // in res_consider.
 
@@ -416,11 +735,7 @@ fn decide_on_matches<'a>(matches: Vec<Match2D<'a>>, res_consider: &mut VecDeque<
                     // We overlap, and the current glyph sequence is still under consideration;
                     // Decide if better, more matching pixels is better, likely a longer
                     // word, or more complex glyph got matched.
-                    let mlen = m
-                        .tokens
-                        .iter()
-                        .map(|z| z.glyph.total())
-                        .fold(0, |x, a| x + a);
+                    let mlen = m.confidence.matched_pixels;
 
                     // Make the decision.
                     let new_is_better = current_matching > mlen;
@@ -540,16 +855,38 @@ pub fn match_resolver<'a>(y: u32, window_size: u32, matches: &[Match<'a>]) -> Ve
                 h: window_size - 1,
             };
 
+            let confidence = Confidence {
+                matched_pixels: glyphs
+                    .iter()
+                    .map(|z| match z.token {
+                        Token::Glyph { glyph, .. } => glyph.total(),
+                        _ => 0,
+                    })
+                    .sum(),
+                ..Default::default()
+            };
+
             // We should insert our current entry.
             res.push(Match2D {
                 tokens: glyphs
                     .iter()
                     .map(|z| match z.token {
-                        Token::Glyph { glyph, label } => LabelledGlyph { glyph, label },
+                        Token::Glyph { glyph, label } => LabelledGlyph {
+                            glyph,
+                            label,
+                            location: Rect {
+                                x: z.position,
+                                y,
+                                w: z.width - 1,
+                                h: window_size - 1,
+                            },
+                        },
                         _ => panic!("should never have whitespace here"),
                     })
                     .collect::<_>(),
+                bounds: this_block_region.clone(),
                 location: this_block_region,
+                confidence,
             });
 
             match_index += untrimmed_length;
@@ -589,21 +926,101 @@ impl LabelledHistogram {
         }
     }
 
-    /// Add pixel adds this pixel to the histogram and adds this value to the current past histogram.
-    fn add_pixel<P: Pixel>(&mut self, x: usize, p: P) -> bool
-    where
-        u8: PartialEq<<P as Pixel>::Subpixel>,
-    {
-        let color = self.label.0;
-        let matches = color.0[0] == p.channels()[0]
-            && color.0[1] == p.channels()[1]
-            && color.0[2] == p.channels()[2];
-        if matches {
-            self.histogram[x] += 1;
-            self.past_histograms.back_mut().unwrap()[x] += 1;
-            return true;
+    /// Add a pixel of this label to the histogram and the current past histogram.
+    fn add_pixel(&mut self, x: usize) {
+        self.histogram[x] += 1;
+        self.past_histograms.back_mut().unwrap()[x] += 1;
+    }
+
+    /// Number of pixels in each of the past histograms, over the columns from x with the width.
+    pub fn row_counts(&self, x: u32, width: u32) -> Vec<HistogramType> {
+        let columns = x as usize..(x + width) as usize;
+        self.past_histograms
+            .iter()
+            .map(|row| row[columns.clone()].iter().sum())
+            .collect()
+    }
+}
+
+/// Classify the pixel to the index of the label it belongs to.
+#[inline]
+fn classify_pixel<P: Pixel<Subpixel = u8>>(classifier: &ColorClassifier, p: &P) -> Option<usize> {
+    let c = p.channels();
+    classifier.classify(Rgb([c[0], c[1], c[2]]))
+}
+
+/// Check that the colors of the exact labels are unique, other rules are allowed to overlap and the
+/// first matching label wins.
+fn check_unique_labels(labels: &[ColorLabel]) -> Result<()> {
+    let mut color_set = std::collections::HashSet::new();
+    for l in labels {
+        if let ColorRule::Exact(color) = l.0 {
+            if !color_set.insert(color) {
+                return Err(Error::DuplicateColorLabel(color));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Counts the pixels of the label with the index in each row of the window, over the columns from
+/// x with the width.
+type RowCounter<'r> = dyn Fn(usize, u32, u32) -> Vec<HistogramType> + 'r;
+
+/// Match glyphs in each of the labelled histograms of the window at y and resolve them into 2d
+/// matches. Matches with glyphs that have a row profile are verified with the row counter, which
+/// also provides the rows to determine the bounds and the confidence of the matches.
+fn window_matches<'a>(
+    y: u32,
+    window_size: u32,
+    histograms: &[LabelledHistogram],
+    matcher: &'a dyn Matcher,
+    rows: &RowCounter,
+) -> Vec<Match2D<'a>> {
+    let mut matches_2d: Vec<Match2D<'a>> = vec![];
+    for (index, labelled_histogram) in histograms.iter().enumerate() {
+        // Find glyphs in the histogram.
+        let matches = decode_histogram(labelled_histogram, matcher);
+
+        // Resolve the found matches and group the consecutive tokens into 2d matches.
+        let mut resolved = match_resolver(y, window_size, &matches);
+        verify_row_profiles(&matches, &mut resolved, |x, width| rows(index, x, width));
+        for m in resolved.iter_mut() {
+            let rows = rows(index, m.location.x, m.location.w + 1);
+            m.tighten(&rows);
+            m.confidence.region_pixels = rows.iter().sum();
+            m.confidence.unmatched_columns = unmatched_columns(m, &labelled_histogram.histogram);
+            m.confidence.ambiguous_tokens = ambiguous_tokens(&m.tokens, matcher);
         }
-        false
+        matches_2d.extend(resolved);
+    }
+    matches_2d
+}
+
+/// Remove the 2d matches that hold a glyph whose row profile doesn't match the pixels in the
+/// rows of the window, as counted over the columns of the glyph.
+fn verify_row_profiles<'a>(
+    matches: &[Match<'a>],
+    matches_2d: &mut Vec<Match2D<'a>>,
+    rows: impl Fn(u32, u32) -> Vec<HistogramType>,
+) {
+    let rejected = matches
+        .iter()
+        .filter(|m| match m.token {
+            Token::Glyph { glyph, .. } => {
+                glyph.row_profile().is_some()
+                    && !glyph.matches_row_profile(&rows(m.position, m.width))
+            }
+            Token::WhiteSpace(_) => false,
+        })
+        .map(|m| m.position)
+        .collect::<Vec<u32>>();
+    if !rejected.is_empty() {
+        matches_2d.retain(|m| {
+            !rejected
+                .iter()
+                .any(|x| *x >= m.location.left() && *x <= m.location.right())
+        });
     }
 }
 
@@ -613,57 +1030,79 @@ pub struct WindowHistogramIterator<'b, I: GenericImageView> {
     y: u32,
     window_size: u32,
     histograms: Vec<LabelledHistogram>,
+    classifier: Cow<'b, ColorClassifier>,
 }
 
 impl<'b, I: GenericImageView> WindowHistogramIterator<'b, I>
 where
-    u8: PartialEq<<<I as GenericImageView>::Pixel as Pixel>::Subpixel>,
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
 {
     /// Construct a new sliding window histogram iterator, this creates the initial histogram state.
+    /// Fails if the colors of the labels are not unique, there are too many labels or the window is
+    /// taller than the image.
     pub fn new(
         image: &'b I,
         labels: &[ColorLabel],
         window_size: u32,
-    ) -> WindowHistogramIterator<'b, I> {
+    ) -> Result<WindowHistogramIterator<'b, I>> {
+        // Check if the colors are unique here.
+        check_unique_labels(labels)?;
+        // Classify each pixel once instead of comparing it against each label.
+        let classifier = ColorClassifier::new(labels)?;
+        Self::from_classifier(image, labels, Cow::Owned(classifier), window_size)
+    }
+
+    /// Like [`WindowHistogramIterator::new`], but with a classifier that was built for the labels
+    /// before, such that all scans with the same labels share it. Fails if the window is taller
+    /// than the image.
+    pub fn with_classifier(
+        image: &'b I,
+        labels: &[ColorLabel],
+        classifier: &'b ColorClassifier,
+        window_size: u32,
+    ) -> Result<WindowHistogramIterator<'b, I>> {
+        Self::from_classifier(image, labels, Cow::Borrowed(classifier), window_size)
+    }
+
+    fn from_classifier(
+        image: &'b I,
+        labels: &[ColorLabel],
+        classifier: Cow<'b, ColorClassifier>,
+        window_size: u32,
+    ) -> Result<WindowHistogramIterator<'b, I>> {
+        if window_size > image.height() {
+            return Err(Error::WindowTallerThanImage {
+                window_size,
+                image_height: image.height(),
+            });
+        }
         let mut histograms: Vec<LabelledHistogram> = Vec::new();
         for l in labels {
             let labelled_histogram = LabelledHistogram {
                 histogram: vec![0; image.width() as usize],
-                label: *l,
+                label: l.clone(),
                 past_histograms: Default::default(),
             };
             histograms.push(labelled_histogram);
         }
-        // Check if the colors are unique here.
-        {
-            let mut color_set = std::collections::HashSet::new();
-            for l in labels {
-                color_set.insert(l.0);
-            }
-            if color_set.len() != labels.len() {
-                panic!("the colors in labels may not hold duplicates");
-            }
-        }
         for y in 0..window_size {
             for h in histograms.iter_mut() {
                 h.add_past();
             }
             for x in 0..image.width() {
-                let p = image.get_pixel(x, y);
-                for h in histograms.iter_mut() {
-                    if h.add_pixel(x as usize, p) {
-                        break;
-                    }
+                if let Some(index) = classify_pixel(&classifier, &image.get_pixel(x, y)) {
+                    histograms[index].add_pixel(x as usize);
                 }
             }
         }
 
-        WindowHistogramIterator {
+        Ok(WindowHistogramIterator {
             image,
             histograms,
+            classifier,
             y: 0,
             window_size,
-        }
+        })
     }
 
     pub fn advance(&mut self) -> bool {
@@ -677,10 +1116,8 @@ where
             for x in 0..self.image.width() {
                 // Add the side moving into the histogram.
                 let p = self.image.get_pixel(x, self.y + self.window_size);
-                for h in self.histograms.iter_mut() {
-                    if h.add_pixel(x as usize, p) {
-                        break;
-                    }
+                if let Some(index) = classify_pixel(&self.classifier, &p) {
+                    self.histograms[index].add_pixel(x as usize);
                 }
             }
 
@@ -701,59 +1138,321 @@ where
 }
 
 /// Function to slide a window over an image and match glyphs for each histogram thats created.
+///
+/// This scans with the default [`ScanOptions`], use a [`Scanner`] to configure the scan.
 pub fn moving_windowed_histogram<'a, I: GenericImageView>(
     image: &I,
     window_size: u32,
     matcher: &'a dyn Matcher,
     labels: &[ColorLabel],
-) -> Vec<Match2D<'a>>
+) -> Result<Vec<Match2D<'a>>>
 where
-    u8: PartialEq<<<I as GenericImageView>::Pixel as Pixel>::Subpixel>,
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
 {
-    let mut res_final: Vec<Match2D<'a>> = Vec::new();
+    check_unique_labels(labels)?;
+    let classifier = ColorClassifier::new(labels)?;
+    scanner::scan(
+        image,
+        matcher,
+        labels,
+        &classifier,
+        &ScanOptions::new(window_size),
+    )
+}
+
+/// Parallel version of [`moving_windowed_histogram`], gives identical results.
+///
+/// The image is split into horizontal bands that overlap by the window size, the windows in each
+/// band are matched concurrently. The matches of all windows are then decided on in order, just
+/// like the single threaded scan does.
+#[cfg(feature = "parallel")]
+pub fn moving_windowed_histogram_parallel<'a, I: GenericImageView + Sync>(
+    image: &I,
+    window_size: u32,
+    matcher: &'a (dyn Matcher + Sync),
+    labels: &[ColorLabel],
+) -> Result<Vec<Match2D<'a>>>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    use rayon::prelude::*;
+
+    check_unique_labels(labels)?;
+    if window_size > image.height() {
+        return Err(Error::WindowTallerThanImage {
+            window_size,
+            image_height: image.height(),
+        });
+    }
+
+    // All bands share the classifier.
+    let classifier = ColorClassifier::new(labels)?;
+
+    // Split the window positions into bands, a few per thread to balance the work.
+    let positions = image.height() - window_size + 1;
+    let band_count = (rayon::current_num_threads() * 4) as u32;
+    let band_size = std::cmp::max(positions.div_ceil(band_count), window_size);
+    let bands = (0..positions)
+        .step_by(band_size as usize)
+        .map(|start| (start, std::cmp::min(start + band_size, positions)))
+        .collect::<Vec<_>>();
+
+    // Collect the matches for each window position of each band.
+    let band_matches = bands
+        .par_iter()
+        .map(|&(start, end)| -> Result<Vec<Vec<Match2D<'a>>>> {
+            let view = image.view(0, start, image.width(), end - start + window_size - 1);
+            let mut iterable =
+                WindowHistogramIterator::with_classifier(&*view, labels, &classifier, window_size)?;
+            let mut res = vec![];
+            loop {
+                let y = start + iterable.y();
+                let histograms = iterable.histograms();
+                let rows = |index: usize, x, width| histograms[index].row_counts(x, width);
+                res.push(window_matches(y, window_size, histograms, matcher, &rows));
+                if y + 1 == end || !iterable.advance() {
+                    break;
+                }
+            }
+            Ok(res)
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    // Container for results under consideration, we check matches against overlap in this window
-    // and keep the parts that are the best matches.
+    let mut res_final: Vec<Match2D<'a>> = Vec::new();
     let mut res_consider: VecDeque<Match2D<'a>> = VecDeque::new();
-    // Once the matches here move out of the window, we move them to res itself.
-
-    // Create our histogram iterator.
-    let mut iterable = WindowHistogramIterator::new(image, labels, window_size);
-    let mut more_to_come = true;
-    while more_to_come {
-        let y = iterable.y();
-        let mut matches_2d: Vec<Match2D<'a>> = vec![];
-        for labelled_histogram in iterable.histograms().iter() {
-            // Find glyphs in the histogram.
-            let matches = bin_glyph_matcher(&labelled_histogram, matcher);
-
-            // Resolve the found matches and group the consecutive tokens into 2d matches.
-            matches_2d.extend(match_resolver(y, window_size, &matches));
-        }
-        // Decide which matches are to be kept.
+    for (y, matches_2d) in band_matches.into_iter().flatten().enumerate() {
         decide_on_matches(matches_2d, &mut res_consider);
+        finalize_considerations(y as u32, &mut res_consider, &mut res_final);
+    }
+    res_final.extend(res_consider.drain(..));
 
-        // Move matches from res_consider to res_final.
-        finalize_considerations(y, &mut res_consider, &mut res_final);
+    Ok(res_final)
+}
 
-        // Only advance afterwards
-        more_to_come = iterable.advance();
+/// Slide a window over only the regions of interest of the image and match glyphs in them.
+///
+/// The regions of interest use their width and height as the size of the region, they are clipped
+/// to the image and regions that are smaller than the window are skipped. Match locations are in
+/// image coordinates and matches from overlapping regions are deduplicated like overlapping
+/// matches in a single scan.
+pub fn moving_windowed_histogram_roi<'a, I: GenericImageView>(
+    image: &I,
+    rois: &[Rect],
+    window_size: u32,
+    matcher: &'a dyn Matcher,
+    labels: &[ColorLabel],
+) -> Result<Vec<Match2D<'a>>>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    check_unique_labels(labels)?;
+    // All regions share the classifier.
+    let classifier = ColorClassifier::new(labels)?;
+    let options = ScanOptions::new(window_size);
+
+    let mut matches: Vec<Match2D<'a>> = vec![];
+    for roi in rois.iter() {
+        if roi.x >= image.width() || roi.y >= image.height() {
+            continue;
+        }
+        let w = std::cmp::min(roi.w, image.width() - roi.x);
+        let h = std::cmp::min(roi.h, image.height() - roi.y);
+        if w == 0 || h < window_size {
+            continue;
+        }
+        let view = image.view(roi.x, roi.y, w, h);
+        for mut m in scanner::scan(&*view, matcher, labels, &classifier, &options)? {
+            m.map_rects(|r| Rect {
+                x: r.x + roi.x,
+                y: r.y + roi.y,
+                ..r.clone()
+            });
+            matches.push(m);
+        }
     }
 
+    // Feed the matches in scan order through the same decision as the sliding window.
+    matches.sort_by_key(|m| (m.location.y, m.location.x));
+    let mut res_final: Vec<Match2D<'a>> = Vec::new();
+    let mut res_consider: VecDeque<Match2D<'a>> = VecDeque::new();
+    for m in matches {
+        finalize_considerations(m.location.y, &mut res_consider, &mut res_final);
+        decide_on_matches(vec![m], &mut res_consider);
+    }
     res_final.extend(res_consider.drain(..));
 
-    res_final
+    Ok(res_final)
+}
+
+/// Slide a window over an image in which the text has the provided orientation.
+///
+/// The image is scanned through an [`orientation::OrientedView`] in which the text reads from left
+/// to right, so the glyph sets of normal text can be used. Match locations are mapped back to the
+/// rectangles they cover in the original image.
+pub fn moving_windowed_histogram_oriented<'a, I: GenericImageView>(
+    image: &I,
+    orientation: Orientation,
+    window_size: u32,
+    matcher: &'a dyn Matcher,
+    labels: &[ColorLabel],
+) -> Result<Vec<Match2D<'a>>>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    let view = orientation::OrientedView::new(image, orientation);
+    let mut matches = moving_windowed_histogram(&view, window_size, matcher, labels)?;
+    for m in matches.iter_mut() {
+        m.map_rects(|r| orientation.rect_to_image(image.dimensions(), r));
+    }
+    Ok(matches)
+}
+
+/// A glyph set to scan for with [`moving_windowed_histogram_multi`], the window size is usually
+/// the line height of the glyph set.
+pub type ScanSet<'a> = (u32, &'a dyn Matcher);
+
+/// Slide the windows of multiple glyph sets over an image in a single pass.
+///
+/// Each row of pixels is classified once and shared by the windows of all glyph sets, which can
+/// have different heights. The matches of each glyph set are decided on separately, so the result
+/// is identical to running [`moving_windowed_histogram`] for each of them, each match is tagged
+/// with the index of the glyph set in `sets`.
+pub fn moving_windowed_histogram_multi<'a, I: GenericImageView>(
+    image: &I,
+    sets: &[ScanSet<'a>],
+    labels: &[ColorLabel],
+) -> Result<Vec<(usize, Match2D<'a>)>>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    check_unique_labels(labels)?;
+    if let Some(&(window_size, _)) = sets.iter().find(|(w, _)| *w > image.height()) {
+        return Err(Error::WindowTallerThanImage {
+            window_size,
+            image_height: image.height(),
+        });
+    }
+
+    /// Histograms and matches under consideration of one glyph set.
+    struct SetState<'a> {
+        histograms: Vec<LabelledHistogram>,
+        res_consider: VecDeque<Match2D<'a>>,
+    }
+    let mut states = sets
+        .iter()
+        .map(|_| SetState {
+            histograms: labels
+                .iter()
+                .map(|l| LabelledHistogram {
+                    histogram: vec![0; image.width() as usize],
+                    label: l.clone(),
+                    past_histograms: Default::default(),
+                })
+                .collect(),
+            res_consider: VecDeque::new(),
+        })
+        .collect::<Vec<_>>();
+
+    // Classified rows, from the row that leaves the windows next up to the lowest row of the
+    // tallest window.
+    let classifier = ColorClassifier::new(labels)?;
+    let mut rows: VecDeque<Vec<Option<usize>>> = VecDeque::new();
+    let mut rows_start = 0;
+    let classify_row = |y: u32| {
+        (0..image.width())
+            .map(|x| classify_pixel(&classifier, &image.get_pixel(x, y)))
+            .collect::<Vec<_>>()
+    };
+    let update = |histograms: &mut [LabelledHistogram], row: &[Option<usize>], add: bool| {
+        for (x, index) in row.iter().enumerate() {
+            if let Some(index) = index {
+                if add {
+                    histograms[*index].histogram[x] += 1;
+                } else {
+                    histograms[*index].histogram[x] -= 1;
+                }
+            }
+        }
+    };
+
+    let mut res_final: Vec<(usize, Match2D<'a>)> = Vec::new();
+    let mut finalized: Vec<Match2D<'a>> = Vec::new();
+    let tallest = sets.iter().map(|(w, _)| *w).max().unwrap_or(0);
+    let positions = sets
+        .iter()
+        .map(|(w, _)| image.height() - w + 1)
+        .max()
+        .unwrap_or(0);
+    for y in 0..positions {
+        let rows_end = std::cmp::min(y + tallest, image.height());
+        while rows_start + (rows.len() as u32) < rows_end {
+            rows.push_back(classify_row(rows_start + rows.len() as u32));
+        }
+
+        for (set_index, (&(window_size, matcher), state)) in
+            sets.iter().zip(states.iter_mut()).enumerate()
+        {
+            if y + window_size > image.height() {
+                continue;
+            }
+            // Move the window down a row, or fill it on the first row.
+            if y == 0 {
+                for row in rows.iter().take(window_size as usize) {
+                    update(&mut state.histograms, row, true);
+                }
+            } else {
+                update(
+                    &mut state.histograms,
+                    &rows[(y - 1 - rows_start) as usize],
+                    false,
+                );
+                let entering = (y + window_size - 1 - rows_start) as usize;
+                update(&mut state.histograms, &rows[entering], true);
+            }
+
+            let window = rows
+                .range((y - rows_start) as usize..)
+                .take(window_size as usize);
+            let rows = |index: usize, x: u32, width: u32| {
+                window
+                    .clone()
+                    .map(|row| {
+                        row[x as usize..(x + width) as usize]
+                            .iter()
+                            .filter(|v| **v == Some(index))
+                            .count() as HistogramType
+                    })
+                    .collect()
+            };
+            let matches_2d = window_matches(y, window_size, &state.histograms, matcher, &rows);
+            decide_on_matches(matches_2d, &mut state.res_consider);
+            finalize_considerations(y, &mut state.res_consider, &mut finalized);
+            // The last window position of this glyph set, accept the remaining matches.
+            if y + window_size == image.height() {
+                finalized.extend(state.res_consider.drain(..));
+            }
+            res_final.extend(finalized.drain(..).map(|m| (set_index, m)));
+        }
+
+        // Rows above the current window position are no longer needed.
+        while rows_start < y {
+            rows.pop_front();
+            rows_start += 1;
+        }
+    }
+
+    Ok(res_final)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use test_util::test_alphabet::{
-        render_standard_alphabet, render_standard_color, standard_alphabet,
+        render_standard_alphabet, render_standard_color, standard_alphabet, standard_glyph_set,
     };
 
     fn simple_histogram_to_bin_histogram(hist: &SimpleHistogram) -> LabelledHistogram {
-        LabelledHistogram::from_u8(hist, (Rgb([255, 255, 255]), 0))
+        LabelledHistogram::from_u8(hist, (Rgb([255, 255, 255]).into(), 0))
     }
 
     #[test]
@@ -869,9 +1568,10 @@ mod tests {
         }
 
         let image = &image;
-        let labels = vec![(white, 0), (red, 1), (blue, 2)];
+        let labels = vec![(white.into(), 0), (red.into(), 1), (blue.into(), 2)];
 
-        let matches = moving_windowed_histogram(image, glyph_set.line_height, &matcher, &labels);
+        let matches =
+            moving_windowed_histogram(image, glyph_set.line_height, &matcher, &labels).unwrap();
 
         if have_dir {
             util::write_match_html(
@@ -925,7 +1625,7 @@ mod tests {
             for t in m.tokens.iter() {
                 for (label_color, label) in labels.iter() {
                     if *label == t.label {
-                        assert_eq!(color, label_color);
+                        assert_eq!(*color, label_color.color());
                     }
                 }
             }
@@ -935,6 +1635,421 @@ mod tests {
         assert_eq!(matches.len(), 0);
     }
 
+    #[test]
+    fn test_moving_window_errors() {
+        let matcher = matcher::LongestGlyphMatcher::new(&[]);
+        let image = image::RgbImage::new(10, 5);
+        let white = Rgb::<u8>([255, 255, 255]);
+
+        let res = moving_windowed_histogram(&image, 6, &matcher, &[(white.into(), 0)]);
+        assert!(matches!(
+            res,
+            Err(Error::WindowTallerThanImage {
+                window_size: 6,
+                image_height: 5
+            })
+        ));
+
+        let res =
+            moving_windowed_histogram(&image, 5, &matcher, &[(white.into(), 0), (white.into(), 1)]);
+        assert!(matches!(res, Err(Error::DuplicateColorLabel(c)) if c == white));
+
+        let res = moving_windowed_histogram(&image, 5, &matcher, &[(white.into(), 0)]);
+        assert!(res.unwrap().is_empty());
+
+        // An image without columns holds no matches, and an empty histogram matches nothing.
+        let empty = image::RgbImage::new(0, 5);
+        let res = moving_windowed_histogram(&empty, 5, &matcher, &[(white.into(), 0)]);
+        assert!(res.unwrap().is_empty());
+        let labelled = LabelledHistogram::from_u8(&[], (white.into(), 0));
+        assert!(bin_glyph_matcher(&labelled, &matcher).is_empty());
+        assert!(search_glyph_matcher(&labelled, &matcher).is_empty());
+        assert!(histogram_glyph_matcher(&[], &Default::default(), 0).is_empty());
+    }
+
+    #[test]
+    fn test_moving_window_color_rules() {
+        let glyph_set = standard_glyph_set();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+        // Text with colors that are slightly off, like in a compressed screenshot.
+        let mut image = image::RgbImage::new(100, 40);
+        render_standard_color(&mut image, 10, 10, "caab", Rgb([252, 3, 1]));
+        render_standard_color(&mut image, 50, 20, "deeb", Rgb([250, 250, 255]));
+
+        let red = Rgb::<u8>([255, 0, 0]);
+        let white = Rgb::<u8>([255, 255, 255]);
+        let exact = vec![(red.into(), 0), (white.into(), 1)];
+        let res =
+            moving_windowed_histogram(&image, glyph_set.line_height, &matcher, &exact).unwrap();
+        assert!(res.is_empty());
+
+        let rules = vec![
+            (
+                ColorRule::Tolerance {
+                    color: red,
+                    tolerance: 5,
+                },
+                0,
+            ),
+            (ColorRule::lab(white, 5.0), 1),
+        ];
+        let res =
+            moving_windowed_histogram(&image, glyph_set.line_height, &matcher, &rules).unwrap();
+        let mut found = res
+            .iter()
+            .map(|m| (m.to_string(), m.tokens[0].label))
+            .collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, vec![("caab".to_owned(), 0), ("deeb".to_owned(), 1)]);
+    }
+
+    #[test]
+    fn test_moving_window_oriented() {
+        let glyph_set = standard_glyph_set();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+        let white = Rgb::<u8>([255, 255, 255]);
+        let labels = vec![(white.into(), 0)];
+
+        let mut image = image::RgbImage::new(60, 30);
+        render_standard_color(&mut image, 5, 3, "deeb", white);
+        render_standard_color(&mut image, 20, 15, "wacb", white);
+        let expected =
+            moving_windowed_histogram(&image, glyph_set.line_height, &matcher, &labels).unwrap();
+        assert_eq!(expected.len(), 2);
+
+        for (orientation, rotated) in [
+            (Orientation::TopToBottom, image::imageops::rotate90(&image)),
+            (Orientation::BottomToTop, image::imageops::rotate270(&image)),
+            (Orientation::Rotated180, image::imageops::rotate180(&image)),
+        ] {
+            // Text that isn't horizontal isn't found without the orientation.
+            let res = moving_windowed_histogram(&rotated, glyph_set.line_height, &matcher, &labels)
+                .unwrap();
+            assert!(res.iter().all(|m| m.to_string() != "deeb"));
+
+            let res = moving_windowed_histogram_oriented(
+                &rotated,
+                orientation,
+                glyph_set.line_height,
+                &matcher,
+                &labels,
+            )
+            .unwrap();
+            assert_eq!(res.len(), expected.len());
+            for m in expected.iter() {
+                let location = orientation.rect_to_image(rotated.dimensions(), &m.location);
+                let found = res.iter().find(|f| f.location == location).unwrap();
+                assert_eq!(found.to_string(), m.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_moving_window_row_profile() {
+        // The n and u have the same column histogram, but a different row profile.
+        let alphabet = test_util::test_alphabet::Alphabet::new(3)
+            .with_letter('n', "xxx\nx.x\nx.x")
+            .with_letter('u', "x.x\nx.x\nxxx");
+        let mut glyph_set = alphabet.glyph_set();
+        glyph_set.entries.retain(|g| g.glyph() == "n");
+
+        let white = Rgb::<u8>([255, 255, 255]);
+        let labels = vec![(white.into(), 0)];
+        let mut image = image::RgbImage::new(30, 10);
+        alphabet.render(&mut image, 2, 1, "nn", white);
+        alphabet.render(&mut image, 15, 5, "un", white);
+
+        let found = |glyph_set: &glyphs::GlyphSet| {
+            let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+            let res = moving_windowed_histogram(&image, 3, &matcher, &labels).unwrap();
+            let mut multi = moving_windowed_histogram_multi(&image, &[(3, &matcher)], &labels)
+                .unwrap()
+                .into_iter()
+                .map(|(_, m)| m)
+                .collect::<Vec<_>>();
+            assert_eq!(multi, res);
+            let mut scanner = frame::FrameScanner::new(matcher.clone(), 3, &labels).unwrap();
+            scanner.scan(&image).unwrap();
+            assert_eq!(scanner.matches(), res);
+            multi.sort_by_key(|m| m.location.x);
+            multi
+                .iter()
+                .map(|m| (m.to_string(), m.location.x, m.location.y))
+                .collect::<Vec<_>>()
+        };
+
+        // Without the row profile the u is taken for an n.
+        assert_eq!(
+            found(&glyph_set),
+            vec![("nn".to_owned(), 3, 1), ("nn".to_owned(), 16, 5)]
+        );
+
+        glyph_set.entries[0].set_row_profile(Some(vec![3, 2, 2]));
+        assert_eq!(found(&glyph_set), vec![("nn".to_owned(), 3, 1)]);
+    }
+
+    #[test]
+    fn test_moving_window_bounds() {
+        let alphabet = test_util::test_alphabet::Alphabet::new(3)
+            .with_letter('o', "xxx\nx.x\nxxx")
+            .with_letter('l', "x\nx\nx");
+        let glyph_set = alphabet.glyph_set();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+        let white = Rgb::<u8>([255, 255, 255]);
+        let labels = vec![(white.into(), 0)];
+        let mut image = image::RgbImage::new(30, 20);
+        alphabet.render(&mut image, 4, 6, "lol", white);
+
+        let rect = |x, y, w, h| Rect { x, y, w, h };
+        let token_locations = |m: &Match2D| {
+            m.tokens
+                .iter()
+                .map(|t| t.location.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // The window is taller than the text, the bounds only cover the rows of the text.
+        let res = moving_windowed_histogram(&image, 5, &matcher, &labels).unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].to_string(), "lol");
+        assert_eq!(res[0].location.h, 4);
+        assert_eq!(res[0].bounds, rect(5, 6, 6, 2));
+        assert_eq!(
+            token_locations(&res[0]),
+            vec![rect(5, 6, 0, 2), rect(7, 6, 2, 2), rect(11, 6, 0, 2)]
+        );
+
+        // Upside down, all rectangles are mapped back to the image.
+        let rotated = image::imageops::rotate180(&image);
+        let res = moving_windowed_histogram_oriented(
+            &rotated,
+            Orientation::Rotated180,
+            5,
+            &matcher,
+            &labels,
+        )
+        .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].bounds, rect(18, 11, 6, 2));
+        assert_eq!(
+            token_locations(&res[0]),
+            vec![rect(24, 11, 0, 2), rect(20, 11, 2, 2), rect(18, 11, 0, 2)]
+        );
+    }
+
+    #[test]
+    fn test_moving_window_confidence() {
+        let alphabet = test_util::test_alphabet::Alphabet::new(3)
+            .with_letter('o', "xxx\nx.x\nxxx")
+            .with_letter('l', "x\nx\nx")
+            .with_letter('I', "x\nx\nx");
+        let glyph_set = alphabet.glyph_set();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+        let white = Rgb::<u8>([255, 255, 255]);
+        let labels = vec![(white.into(), 0)];
+        let mut image = image::RgbImage::new(30, 20);
+        alphabet.render(&mut image, 4, 6, "lol", white);
+        alphabet.render(&mut image, 4, 13, "oo", white);
+        // A stray pixel in the gap between the o's, in the row above them.
+        image.put_pixel(8, 12, white);
+
+        let mut res = moving_windowed_histogram(&image, 4, &matcher, &labels).unwrap();
+        res.sort_by_key(|m| m.location.y);
+        assert_eq!(res.len(), 2);
+
+        // The l's can't be told apart from the I's.
+        assert_eq!(res[0].to_string(), "IoI");
+        let expected = Confidence {
+            matched_pixels: 14,
+            region_pixels: 14,
+            unmatched_columns: 0,
+            ambiguous_tokens: 2,
+        };
+        assert_eq!(res[0].confidence, expected);
+        assert_eq!(res[0].confidence.coverage(), 1.0);
+
+        assert_eq!(res[1].to_string(), "oo");
+        let expected = Confidence {
+            matched_pixels: 16,
+            region_pixels: 17,
+            unmatched_columns: 1,
+            ambiguous_tokens: 0,
+        };
+        assert_eq!(res[1].confidence, expected);
+        assert_eq!(res[1].confidence.coverage(), 16.0 / 17.0);
+    }
+
+    #[test]
+    fn test_moving_window_multi() {
+        let glyph_set = standard_glyph_set();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+        let small = test_util::test_alphabet::Alphabet::new(3)
+            .with_space_width(2)
+            .with_letter('o', "xxx\nx.x\nxxx")
+            .with_letter('l', "x\nx\nx");
+        let small_glyph_set = small.glyph_set();
+        let small_matcher = matcher::LongestGlyphMatcher::new(&small_glyph_set.entries);
+
+        let white = Rgb::<u8>([255, 255, 255]);
+        let red = Rgb::<u8>([255, 0, 0]);
+        let labels = vec![(white.into(), 0), (red.into(), 1)];
+        let mut image = image::RgbImage::new(100, 40);
+        render_standard_color(&mut image, 5, 3, "deeb", white);
+        small.render(&mut image, 60, 5, "lol", red);
+        render_standard_color(&mut image, 40, 25, "wacb", red);
+        small.render(&mut image, 10, 30, "ool", white);
+
+        let sets: Vec<ScanSet> = vec![
+            (glyph_set.line_height, &matcher),
+            (small_glyph_set.line_height, &small_matcher),
+        ];
+        let res = moving_windowed_histogram_multi(&image, &sets, &labels).unwrap();
+
+        // Identical to scanning for each glyph set separately.
+        for (set_index, (window_size, matcher)) in sets.iter().enumerate() {
+            let expected =
+                moving_windowed_histogram(&image, *window_size, *matcher, &labels).unwrap();
+            let found = res
+                .iter()
+                .filter(|(i, _)| *i == set_index)
+                .map(|(_, m)| m.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected);
+        }
+        let found = |set_index: usize| {
+            res.iter()
+                .filter(|(i, _)| *i == set_index)
+                .map(|(_, m)| m.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(found(0).contains(&"deeb".to_owned()));
+        assert!(found(0).contains(&"wacb".to_owned()));
+        assert!(found(1).contains(&"lol".to_owned()));
+        assert!(found(1).contains(&"ool".to_owned()));
+
+        let too_tall: Vec<ScanSet> = vec![(glyph_set.line_height, &matcher), (41, &small_matcher)];
+        assert!(matches!(
+            moving_windowed_histogram_multi(&image, &too_tall, &labels),
+            Err(Error::WindowTallerThanImage {
+                window_size: 41,
+                image_height: 40
+            })
+        ));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_moving_window_parallel() {
+        let glyph_set = standard_glyph_set();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+        let white = Rgb::<u8>([255, 255, 255]);
+        let red = Rgb::<u8>([255, 0, 0]);
+        let labels = vec![(white.into(), 0), (red.into(), 1)];
+        let mut image = image::RgbImage::new(200, 300);
+        for i in 0..25u32 {
+            let color = if i % 2 == 0 { white } else { red };
+            render_standard_color(&mut image, (i * 37) % 150, i * 11 + (i % 3), "wacb", color);
+            render_standard_color(&mut image, (i * 53) % 150, i * 11 + 4, "deeb", color);
+        }
+
+        let expected =
+            moving_windowed_histogram(&image, glyph_set.line_height, &matcher, &labels).unwrap();
+        assert!(!expected.is_empty());
+        for window_size in [glyph_set.line_height, 1, 300] {
+            let expected =
+                moving_windowed_histogram(&image, window_size, &matcher, &labels).unwrap();
+            let res =
+                moving_windowed_histogram_parallel(&image, window_size, &matcher, &labels).unwrap();
+            assert_eq!(res, expected);
+        }
+
+        let res = moving_windowed_histogram_parallel(&image, 301, &matcher, &labels);
+        assert!(matches!(res, Err(Error::WindowTallerThanImage { .. })));
+    }
+
+    #[test]
+    fn test_moving_window_roi() {
+        let glyph_set = standard_glyph_set();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+        let white = Rgb::<u8>([255, 255, 255]);
+        let red = Rgb::<u8>([255, 0, 0]);
+        let labels = vec![(white.into(), 0), (red.into(), 1)];
+        let mut image = image::RgbImage::new(200, 100);
+        render_standard_color(&mut image, 10, 10, "caab", red);
+        render_standard_color(&mut image, 50, 13, "deeb", white);
+        render_standard_color(&mut image, 10, 60, "wacb", white);
+
+        let full =
+            moving_windowed_histogram(&image, glyph_set.line_height, &matcher, &labels).unwrap();
+        let find = |s: &str| full.iter().find(|m| m.to_string() == s).unwrap().clone();
+
+        // Two overlapping regions around the top texts, one region without text, one region that
+        // is smaller than the window and one that extends beyond the image.
+        let rois = [
+            Rect {
+                x: 5,
+                y: 5,
+                w: 50,
+                h: 20,
+            },
+            Rect {
+                x: 0,
+                y: 0,
+                w: 100,
+                h: 30,
+            },
+            Rect {
+                x: 100,
+                y: 50,
+                w: 50,
+                h: 40,
+            },
+            Rect {
+                x: 0,
+                y: 60,
+                w: 50,
+                h: 3,
+            },
+            Rect {
+                x: 190,
+                y: 90,
+                w: 50,
+                h: 50,
+            },
+        ];
+        let res =
+            moving_windowed_histogram_roi(&image, &rois, glyph_set.line_height, &matcher, &labels)
+                .unwrap();
+        assert_eq!(res.len(), 2);
+        assert!(res.contains(&find("caab")));
+        assert!(res.contains(&find("deeb")));
+
+        // A region that cuts through text keeps the best match from the other region.
+        let rois = [
+            Rect {
+                x: 0,
+                y: 55,
+                w: 60,
+                h: 20,
+            },
+            Rect {
+                x: 0,
+                y: 55,
+                w: 20,
+                h: 20,
+            },
+        ];
+        let res =
+            moving_windowed_histogram_roi(&image, &rois, glyph_set.line_height, &matcher, &labels)
+                .unwrap();
+        assert_eq!(res, vec![find("wacb")]);
+    }
+
     #[test]
     fn histogram_matcher_real() {
         // Somehow... this fails :\
@@ -1025,6 +2140,99 @@ mod tests {
         }
     }
 
+    #[test]
+    fn search_glyph_matcher_longest_not_best() {
+        // The example from the comment above bin_glyph_matcher.
+        let mut glyph_set: glyphs::GlyphSet = Default::default();
+        glyph_set
+            .entries
+            .push(glyphs::Glyph::new(&[0, 2, 3, 3], "a"));
+        glyph_set.entries.push(glyphs::Glyph::new(&[0, 2, 3], "b"));
+        glyph_set
+            .entries
+            .push(glyphs::Glyph::new(&[3, 4, 5, 6], "c"));
+        glyph_set.prepare();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+        let binned = simple_histogram_to_bin_histogram(&vec![0, 2, 3, 3, 4, 5, 6, 0, 0]);
+
+        // The greedy matcher takes a and then can't match the remainder.
+        let matches = bin_glyph_matcher(&binned, &matcher);
+        let matches_2d = match_resolver(0, 1, &matches);
+        assert_eq!(matches_2d.len(), 1);
+        assert_eq!(matches_2d[0].to_string(), "a");
+
+        // Searching finds b followed by c.
+        let matches = search_glyph_matcher(&binned, &matcher);
+        let matches_2d = match_resolver(0, 1, &matches);
+        assert_eq!(matches_2d.len(), 1);
+        assert_eq!(matches_2d[0].to_string(), "bc");
+        assert_eq!(matches_2d[0].location.x, 0);
+        assert_eq!(matches_2d[0].location.w, 6);
+    }
+
+    #[test]
+    fn search_glyph_matcher_real_longest_char_not_best() {
+        // Same data as histogram_matching_real_longest_char_not_best.
+        let input: Vec<u8> = vec![
+            0, 0, 0, 0, 13, 0, 0, 0, 0, 0, 0, 0, 0, 5, 2, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0, 0,
+        ];
+        let s1: Vec<u8> = vec![0, 0, 0, 0, 0, 13, 0, 0, 0, 0, 0];
+        let s2: Vec<u8> = vec![0, 0, 0, 5, 2, 2, 2, 2, 2, 2, 2, 0, 0, 0];
+        let s3: Vec<u8> = vec![0, 0, 0, 0, 0, 13, 0, 0, 0, 0, 0, 0];
+
+        let mut glyph_set: glyphs::GlyphSet = Default::default();
+        glyph_set.entries.push(glyphs::Glyph::new(&s1, "s1"));
+        glyph_set.entries.push(glyphs::Glyph::new(&s2, "s2"));
+        glyph_set.entries.push(glyphs::Glyph::new(&s3, "s3"));
+        glyph_set.line_height = 1;
+        glyph_set.prepare();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+        let binned = simple_histogram_to_bin_histogram(&input);
+        let matches = search_glyph_matcher(&binned, &matcher);
+
+        let glyphs = matches
+            .iter()
+            .filter_map(|m| match m.token {
+                Token::Glyph { glyph, .. } => Some(glyph),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(glyphs, vec![&glyph_set.entries[0], &glyph_set.entries[1]]);
+    }
+
+    #[test]
+    fn test_search_glyph_matcher_whitespace() {
+        let glyph_set = test_util::test_alphabet::standard_glyph_set();
+        let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+
+        // Greedy matching is optimal for the alphabet, searching gives the same glyphs and
+        // whitespace.
+        let hist = image_to_simple_histogram(&render_standard_alphabet(), Rgb([255, 255, 255]));
+        let binned = simple_histogram_to_bin_histogram(&hist);
+        let matches = bin_glyph_matcher(&binned, &matcher);
+        assert_eq!(search_glyph_matcher(&binned, &matcher), matches);
+
+        // Also for wider gaps between the glyphs, which hold whitespace of different widths.
+        let mut hist: Vec<u8> = vec![0, 0, 0];
+        for (i, glyph) in glyph_set.entries.iter().take(4).enumerate() {
+            hist.extend(glyph.hist());
+            hist.extend(std::iter::repeat_n(0, i + 1));
+        }
+        let binned = simple_histogram_to_bin_histogram(&hist);
+        let matches = bin_glyph_matcher(&binned, &matcher);
+        let widths = matches
+            .iter()
+            .filter_map(|m| match m.token {
+                Token::WhiteSpace(w) => Some(w),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(widths, vec![2, 0, 1, 2]);
+        assert_eq!(search_glyph_matcher(&binned, &matcher), matches);
+    }
+
     #[test]
     fn matching_with_space_character() {
         // Some dummy glyphs
@@ -1157,9 +2365,10 @@ mod tests {
 
         let matcher = matcher::LongestGlyphMatcher::new(&glyph_set.entries);
         let image = &scaled_readme_glyphs;
-        let labels = vec![(Rgb::<u8>([255, 255, 255]), 0)];
+        let labels = vec![(Rgb::<u8>([255, 255, 255]).into(), 0)];
 
-        let matches = moving_windowed_histogram(image, glyph_set.line_height, &matcher, &labels);
+        let matches =
+            moving_windowed_histogram(image, glyph_set.line_height, &matcher, &labels).unwrap();
 
         // We should draw a grid here.
         let bottom = 26 * scale_factor;
@@ -1,139 +1,456 @@
-use crate::glyphs::Glyph;
+use std::sync::Arc;
 
-/// A node in the lookup table tree.
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+use crate::glyphs::Glyph;
+use crate::Decoder;
+
+/// A node in the lookup table, its children are a range of the matcher's nodes and its leafs are
+/// a range of the matcher's leafs. An empty node denotes the absence of a child.
+///
+/// A tail node holds a single glyph, instead of children it has the remainder of the glyph's
+/// histogram as a range of the matcher's tails, the glyph is the leaf at leafs_start.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct LookupNode {
-    /// Vector holding children, indexed by value in this histogram bin.
-    children: Vec<Option<LookupNode>>,
-    /// Histogram index where this node is located.
-    index: usize,
-    /// Glyphs that terminate at this node because of length.
-    leafs: Vec<Glyph>,
-    /// All glyphs that are in this node and its descendents.
-    glyphs: Vec<Glyph>,
+    /// Start of the children in the nodes, these are indexed by value in the histogram bin. For a
+    /// tail node this is the start of the tail.
+    pub(crate) children_start: u32,
+    /// Number of children, one more than the largest value in the bin of this node's glyphs.
+    pub(crate) children_len: u32,
+    /// Start of the glyphs that terminate at this node because of length.
+    pub(crate) leafs_start: u32,
+    /// Number of glyphs that terminate at this node, zero for a tail node.
+    pub(crate) leafs_len: u32,
+    /// Number of histogram bins in the tail, zero if this is not a tail node.
+    pub(crate) tail_len: u32,
+}
+
+impl LookupNode {
+    /// Whether this is an absent child, every node in the tree has children, leafs or a tail.
+    fn is_absent(&self) -> bool {
+        self.children_len == 0 && self.leafs_len == 0 && self.tail_len == 0
+    }
 }
 
 /// A lookup table based glyph matcher that jumps to offsets based on histogram values.
+///
+/// The nodes are stored in a flat array with the root first, the children of a node are stored
+/// next to each other after their parent such that each step is a single lookup. Leafs refer to
+/// glyphs by index, each glyph is stored once and the glyphs can be shared with other matchers.
+///
+/// If the matcher is prepared as minimal, the tree is cut short when a single glyph remains and
+/// the remainder of that glyph's histogram is compared directly. This gives the same results with
+/// a much smaller tree.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct LookupMatcher {
-    pub tree: LookupNode,
+    /// The glyphs the matcher was prepared with.
+    pub(crate) glyphs: Arc<[Glyph]>,
+    /// The nodes of the tree, the root is the first node.
+    pub(crate) nodes: Vec<LookupNode>,
+    /// Glyph indices of the leafs of all nodes, within a node in the order of the original glyphs.
+    pub(crate) leafs: Vec<u32>,
+    /// Remaining histogram bins of the glyphs in the tail nodes.
+    pub(crate) tails: Vec<u8>,
 }
 
-fn recurse_glyph_matching(n: &mut LookupNode, glyphs: &[Glyph], index: usize, stripped: bool) {
-    // Iterate through all the glyph indices in this node.
-    for glyph in n.glyphs.iter() {
-        let hist = if stripped {
-            if let Some(h) = glyph.lstrip_hist() {
+/// The histogram of the glyph, or None if the glyph has no lstrip histogram in stripped mode.
+fn glyph_hist(glyph: &Glyph, stripped: bool) -> Option<&[u8]> {
+    if stripped {
+        glyph.lstrip_hist()
+    } else {
+        Some(glyph.hist())
+    }
+}
+
+/// Tolerance to use when matching histograms that may deviate from the glyph's histogram.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Tolerance {
+    /// Maximum difference allowed between a single histogram bin and the glyph's bin.
+    pub per_bin: u32,
+    /// Maximum summed difference over all bins of a glyph.
+    pub total: u32,
+}
+
+impl LookupMatcher {
+    /// Prepare the glyph matcher from a set of glyphs.
+    /// If stripped is true, lstrip_hist is used.
+    /// If minimal is true, the decision graph is cut short if only one glyph remains.
+    pub fn prepare(&mut self, glyphs: &[Glyph], stripped: bool, minimal: bool) {
+        self.prepare_shared(glyphs.into(), stripped, minimal);
+    }
+
+    /// Prepare the glyph matcher like prepare does, without copying the glyphs.
+    pub fn prepare_shared(&mut self, glyphs: Arc<[Glyph]>, stripped: bool, minimal: bool) {
+        *self = Default::default();
+        self.glyphs = glyphs;
+        // Assign the first node with all possible glyph indices and build out the tree from the
+        // first histogram index. The tree is built breadth first, such that the nodes near the
+        // root that are visited by most searches are close together.
+        let all = (0..self.glyphs.len() as u32).collect::<Vec<u32>>();
+        self.nodes.push(Default::default());
+        let mut queue = std::collections::VecDeque::from([(0, all, 0)]);
+        while let Some((id, members, index)) = queue.pop_front() {
+            for (child, group) in self.build_node(id, &members, index, stripped, minimal) {
+                queue.push_back((child, group, index + 1));
+            }
+        }
+    }
+
+    /// Fill in the node at this position for these glyphs, returns the positions of the children
+    /// with their glyphs.
+    fn build_node(
+        &mut self,
+        id: usize,
+        members: &[u32],
+        index: usize,
+        stripped: bool,
+        minimal: bool,
+    ) -> Vec<(usize, Vec<u32>)> {
+        let leafs_start = self.leafs.len() as u32;
+
+        // A single glyph that doesn't terminate here becomes a tail node.
+        if let (true, &[g]) = (minimal, members) {
+            let hist = glyph_hist(&self.glyphs[g as usize], stripped).unwrap_or_default();
+            if hist.len() > index {
+                let tail_start = self.tails.len() as u32;
+                self.tails.extend_from_slice(&hist[index..]);
+                self.leafs.push(g);
+                self.nodes[id] = LookupNode {
+                    children_start: tail_start,
+                    leafs_start,
+                    tail_len: (hist.len() - index) as u32,
+                    ..Default::default()
+                };
+                return vec![];
+            }
+        }
+
+        // Group the glyphs by their histogram value at the index being considered, glyphs whose
+        // histogram is not longer than the index terminate here.
+        let mut groups: Vec<Vec<u32>> = vec![];
+        for &g in members.iter() {
+            let hist = if let Some(h) = glyph_hist(&self.glyphs[g as usize], stripped) {
                 h
             } else {
                 continue; // no lstrip histogram, skip this in lstrip situations.
+            };
+            if hist.len() <= index {
+                self.leafs.push(g);
+                continue;
             }
-        } else {
-            glyph.hist()
+            let v = hist[index] as usize;
+            if groups.len() <= v {
+                groups.resize(v + 1, vec![]);
+            }
+            groups[v].push(g);
+        }
+
+        // The children are appended, so they always come after their parent.
+        let children_start = self.nodes.len();
+        self.nodes
+            .resize(children_start + groups.len(), Default::default());
+        self.nodes[id] = LookupNode {
+            children_start: children_start as u32,
+            children_len: groups.len() as u32,
+            leafs_start,
+            leafs_len: self.leafs.len() as u32 - leafs_start,
+            tail_len: 0,
         };
 
-        // Skip this glyph if its histogram is smaller than the current index being assigned.
-        if hist.len() <= index {
-            n.leafs.push(glyph.clone());
-            continue;
-        }
+        // The tree terminates when there are no more groups (at the leafs).
+        groups
+            .into_iter()
+            .enumerate()
+            .filter(|(_, group)| !group.is_empty())
+            .map(|(v, group)| (children_start + v, group))
+            .collect()
+    }
 
-        // Determine the which position this glyph will have in the children vector, based on its
-        // histogram value at the index being considered.
-        let pos_in_children = hist[index] as usize;
+    /// The glyphs this matcher was prepared with, glyph indices refer to these.
+    pub fn glyphs(&self) -> &[Glyph] {
+        &self.glyphs
+    }
 
-        // Ensure the children vector is appropriate size.
-        n.children
-            .resize(std::cmp::max(n.children.len(), pos_in_children + 1), None);
+    /// Number of nodes in the lookup tree, without the slots of absent children.
+    pub fn node_count(&self) -> usize {
+        self.nodes.iter().skip(1).filter(|n| !n.is_absent()).count() + self.nodes.len().min(1)
+    }
 
-        // If it is currently a None value, create a child entry in this slot.
-        if let None = &n.children[pos_in_children] {
-            n.children[pos_in_children] = Some(Default::default());
+    /// The child of the node for this histogram value.
+    fn child(&self, node: u32, v: usize) -> Option<u32> {
+        let n = &self.nodes[node as usize];
+        if v >= n.children_len as usize {
+            return None;
         }
+        let c = n.children_start + v as u32;
+        (!self.nodes[c as usize].is_absent()).then_some(c)
+    }
 
-        // Finally, assign this glyph index into this child.
-        if let Some(child) = n.children[pos_in_children].as_mut() {
-            child.index = index;
-            child.glyphs.push(glyph.clone());
-        }
+    /// Indices of the glyphs that terminate at the node.
+    fn node_leafs(&self, node: u32) -> &[u32] {
+        self.leafs_of(&self.nodes[node as usize])
     }
 
-    // Finally, iterate over all the populated entries in the children vector
-    // and ensure they get populated, recursing. Recursion terminates when the
-    // children vector stays empty (at the leafs).
-    for child in n.children.iter_mut() {
-        if let Some(mut child_node) = child.as_mut() {
-            recurse_glyph_matching(&mut child_node, &glyphs, index + 1, stripped);
-        }
+    /// Indices of the glyphs that terminate at the node.
+    fn leafs_of(&self, n: &LookupNode) -> &[u32] {
+        &self.leafs[n.leafs_start as usize..(n.leafs_start + n.leafs_len) as usize]
     }
-}
 
-impl LookupMatcher {
-    /// Prepare the glyph matcher from a set of glyphs.
-    /// If stripped is true, lstrip_hist is used.
-    /// If minimal is true, the decision graph is cut short if only one glyph remains.
-    pub fn prepare(&mut self, glyphs: &[Glyph], stripped: bool) {
-        // Assign the first node with all possible glyph indices.
-        self.tree.glyphs = glyphs.iter().cloned().collect::<_>();
-        // recurse from the first index and build out the tree.
-        recurse_glyph_matching(&mut self.tree, &glyphs, 0, stripped);
+    /// The remainder of the histogram of the glyph in a tail node.
+    fn node_tail(&self, n: &LookupNode) -> &[u8] {
+        &self.tails[n.children_start as usize..(n.children_start + n.tail_len) as usize]
+    }
+
+    /// Index of the glyph in a tail node.
+    fn tail_glyph(&self, n: &LookupNode) -> u32 {
+        self.leafs[n.leafs_start as usize]
+    }
+
+    /// Number of bins at the start of the histogram that are equal to the tail of the node.
+    fn tail_matched(&self, n: &LookupNode, histogram: &[crate::HistogramType]) -> usize {
+        self.node_tail(n)
+            .iter()
+            .zip(histogram.iter())
+            .take_while(|(t, h)| **t as crate::HistogramType == **h)
+            .count()
     }
 
     /// Find a glyph matching the provided histogram. Returns None if no glyph exactly matches this
     /// histogram, if multiple glyphs would match perfectly it returns the one that occured earliest
     /// in the original slice used to setup the glyph matcher.
+    #[inline]
     pub fn find_match(&self, histogram: &[crate::HistogramType]) -> Option<&Glyph> {
-        let mut c: &LookupNode = &self.tree;
-        let mut best: &LookupNode = c;
-
-        macro_rules! return_best {
-            ( ) => {
-                return match best.leafs.get(0) {
-                    Some(g) => Some(g),
-                    _ => None,
-                }
-            };
-        }
+        self.find_match_index(histogram).map(|i| &self.glyphs[i])
+    }
+
+    /// Like find_match, but returns the index of the glyph in the slice used to setup the matcher.
+    #[inline]
+    pub fn find_match_index(&self, histogram: &[crate::HistogramType]) -> Option<usize> {
+        let mut c: &LookupNode = self.nodes.first()?;
+        // Position in the leafs of the best match so far, the first leaf of the deepest node.
+        let mut best: Option<u32> = None;
 
         // Iterate through the values in d.
-        for b in histogram.iter() {
+        let mut values = histogram.iter();
+        while let Some(b) = values.next() {
             // Use the value in the histrogram as index.
-            let v = *b as usize;
-
-            if c.children.len() == 0 {
-                // Reached a leaf in the tree, return the glyph... we must have one, otherwise 'c'
-                // would be None.
-                // We may have multiple though, in that case the glyph set is ambiguous.
-                return match c.leafs.get(0) {
-                    Some(g) => Some(g),
-                    _ => None,
-                };
+            let v = *b;
+
+            if c.children_len == 0 {
+                if c.tail_len != 0 {
+                    // Only one glyph remains, compare its remaining histogram directly. Like at
+                    // the end of the tree, the glyph only matches if the histogram continues past
+                    // it.
+                    let rest = &histogram[histogram.len() - values.as_slice().len() - 1..];
+                    let matched = self.tail_matched(c, rest);
+                    if matched < c.tail_len as usize && matched < rest.len() {
+                        return best.map(|l| self.leafs[l as usize] as usize);
+                    }
+                    if rest.len() > c.tail_len as usize {
+                        return Some(self.tail_glyph(c) as usize);
+                    }
+                    return None;
+                }
+                // Reached a leaf in the tree, return the glyph.
+                // We may have multiple though, in that case the glyph set is ambiguous. The root
+                // of an empty tree has no leafs at all.
+                return self.leafs_of(c).first().map(|l| *l as usize);
             }
 
-            // If v exceeds the length of children, we terminate the search and return the
+            // If v exceeds the number of children, we terminate the search and return the
             // best matching token so far.
-            if v >= c.children.len() {
-                return_best!();
+            if v >= c.children_len {
+                return best.map(|l| self.leafs[l as usize] as usize);
             }
 
-            // Check if we have a new node in our search tree at this histogram value.
-            if let Some(ref new_c) = c.children[v] {
-                c = new_c; // assign new position.
+            // Check if we have a new node in our search tree at this histogram value, an absent
+            // node means there's no glyph, return the best matching glyph, or none.
+            c = &self.nodes[(c.children_start + v) as usize];
+            if c.is_absent() {
+                return best.map(|l| self.leafs[l as usize] as usize);
+            }
 
-                // If we would have a leaf here, assign it to the best match, because this is a
-                // valid match, but we'll continue searching to find a longer match.
-                if !new_c.leafs.is_empty() {
-                    best = new_c;
+            // If we would have a leaf here, assign it to the best match, because this is a valid
+            // match, but we'll continue searching to find a longer match.
+            if c.leafs_len != 0 {
+                best = Some(c.leafs_start);
+            }
+        }
+        None
+    }
+
+    /// Find all glyphs whose histogram is a prefix of the provided histogram. The glyphs are
+    /// ordered by length, shortest first, glyphs with identical histograms are all returned.
+    pub fn find_all_matches(&self, histogram: &[crate::HistogramType]) -> Vec<&Glyph> {
+        let mut res: Vec<&Glyph> = vec![];
+        if self.nodes.is_empty() {
+            return res;
+        }
+        let mut c: u32 = 0;
+        for (i, b) in histogram.iter().enumerate() {
+            let n = &self.nodes[c as usize];
+            if n.tail_len != 0 {
+                // The glyph of the tail matches if its remaining histogram is a prefix.
+                if self.tail_matched(n, &histogram[i..]) == n.tail_len as usize {
+                    res.push(&self.glyphs[self.tail_glyph(n) as usize]);
                 }
+                break;
+            }
+            if let Some(new_c) = self.child(c, *b as usize) {
+                c = new_c;
+                // Every leaf we pass on the way is a glyph that matches this histogram.
+                res.extend(self.node_leafs(c).iter().map(|g| &self.glyphs[*g as usize]));
             } else {
-                // We got a none, return best matching glyph, or none.
-                return_best!();
+                break;
             }
         }
-        None
+        res
+    }
+
+    /// Number of glyphs in the matcher whose histogram is identical to the provided histogram.
+    pub fn count_identical(&self, hist: &[u8]) -> usize {
+        if self.nodes.is_empty() {
+            return 0;
+        }
+        let mut c: u32 = 0;
+        for (i, b) in hist.iter().enumerate() {
+            let n = &self.nodes[c as usize];
+            if n.tail_len != 0 {
+                // Only the glyph of the tail remains.
+                return (self.node_tail(n) == &hist[i..]) as usize;
+            }
+            if let Some(new_c) = self.child(c, *b as usize) {
+                c = new_c;
+            } else {
+                return 0;
+            }
+        }
+        self.node_leafs(c).len()
+    }
+
+    /// Visit all glyphs that match the start of the provided histogram while allowing for
+    /// deviations in the bins. Neighbouring children are explored as long as the difference in
+    /// each bin stays within the per bin tolerance and the summed difference stays within the total
+    /// tolerance. The function is called with the glyph index, its summed difference and its
+    /// length.
+    fn visit_tolerant<F: FnMut(u32, u32, usize)>(
+        &self,
+        histogram: &[crate::HistogramType],
+        tolerance: &Tolerance,
+        f: &mut F,
+    ) {
+        fn recurser<F: FnMut(u32, u32, usize)>(
+            m: &LookupMatcher,
+            n: u32,
+            histogram: &[crate::HistogramType],
+            consumed: usize,
+            error: u32,
+            tolerance: &Tolerance,
+            f: &mut F,
+        ) {
+            // Glyphs that terminate here have a length equal to the bins consumed so far.
+            for g in m.node_leafs(n) {
+                f(*g, error, consumed);
+            }
+
+            let node = &m.nodes[n as usize];
+            if node.tail_len != 0 {
+                // Only one glyph remains, sum the differences with its remaining histogram.
+                let end = consumed + node.tail_len as usize;
+                if end > histogram.len() {
+                    return;
+                }
+                let mut tail_error = error;
+                for (t, h) in m
+                    .node_tail(node)
+                    .iter()
+                    .zip(histogram[consumed..end].iter())
+                {
+                    let d = (*t as u32).abs_diff(*h);
+                    if d > tolerance.per_bin {
+                        return;
+                    }
+                    tail_error += d;
+                }
+                if tail_error <= tolerance.total {
+                    f(m.tail_glyph(node), tail_error, end);
+                }
+                return;
+            }
+
+            let children_len = node.children_len;
+            if consumed >= histogram.len() || children_len == 0 {
+                return;
+            }
+
+            // Explore all children that are within the tolerance of the histogram value.
+            let v = histogram[consumed];
+            let low = v.saturating_sub(tolerance.per_bin) as usize;
+            let high = std::cmp::min(
+                v.saturating_add(tolerance.per_bin) as usize,
+                children_len as usize - 1,
+            );
+            for i in low..=high {
+                if let Some(child) = m.child(n, i) {
+                    let child_error = error + (i as u32).abs_diff(v);
+                    if child_error <= tolerance.total {
+                        recurser(m, child, histogram, consumed + 1, child_error, tolerance, f);
+                    }
+                }
+            }
+        }
+
+        if !self.nodes.is_empty() {
+            recurser(self, 0, histogram, 0, 0, tolerance, f);
+        }
+    }
+
+    /// Find the glyph that best matches the provided histogram while allowing for deviations in
+    /// the bins, see [`find_all_matches_tolerant`](Self::find_all_matches_tolerant) for the
+    /// allowed deviations. Like [`find_match`](Self::find_match) the longest glyph is returned,
+    /// together with its summed difference, if multiple glyphs have the same length the one with
+    /// the lowest difference is returned. A short glyph that matches exactly therefore doesn't
+    /// displace a longer glyph that is within the tolerance.
+    pub fn find_match_tolerant(
+        &self,
+        histogram: &[crate::HistogramType],
+        tolerance: &Tolerance,
+    ) -> Option<(&Glyph, u32)> {
+        // Best glyph index so far, with its error and length.
+        let mut best: Option<(u32, u32, usize)> = None;
+        self.visit_tolerant(histogram, tolerance, &mut |g, error, length| {
+            let is_better = match best {
+                Some((_, best_error, best_length)) => {
+                    length > best_length || (length == best_length && error < best_error)
+                }
+                None => true,
+            };
+            if is_better {
+                best = Some((g, error, length));
+            }
+        });
+        best.map(|(g, error, _)| (&self.glyphs[g as usize], error))
+    }
+
+    /// Find all glyphs that match the start of the provided histogram while allowing for
+    /// deviations in the bins. Neighbouring children are explored as long as the difference in
+    /// each bin stays within the per bin tolerance and the summed difference stays within the total
+    /// tolerance. The glyphs are ordered by length, shortest first, and then by their summed
+    /// difference, which is returned with them.
+    pub fn find_all_matches_tolerant(
+        &self,
+        histogram: &[crate::HistogramType],
+        tolerance: &Tolerance,
+    ) -> Vec<(&Glyph, u32)> {
+        let mut res: Vec<(u32, u32, usize)> = vec![];
+        self.visit_tolerant(histogram, tolerance, &mut |g, error, length| {
+            res.push((g, error, length));
+        });
+        res.sort_by_key(|(_, error, length)| (*length, *error));
+        res.iter()
+            .map(|(g, error, _)| (&self.glyphs[*g as usize], *error))
+            .collect()
     }
 
-    pub fn to_dot(&self, glyphs: &[Glyph]) -> String {
+    pub fn to_dot(&self, _glyphs: &[Glyph]) -> String {
         let mut res: String = String::new();
         res.push_str(
             r#"digraph g {
@@ -152,63 +469,79 @@ impl LookupMatcher {
             "#,
         );
 
-        fn recurser(glyphs: &[Glyph], r: &mut String, n: &LookupNode, index: usize) {
+        // Number of glyphs in the node and its descendents.
+        fn glyph_count(m: &LookupMatcher, id: u32) -> usize {
+            let n = &m.nodes[id as usize];
+            n.leafs_len as usize
+                + (n.tail_len != 0) as usize
+                + (0..n.children_len as usize)
+                    .filter_map(|i| m.child(id, i))
+                    .map(|c| glyph_count(m, c))
+                    .sum::<usize>()
+        }
+
+        fn recurser(m: &LookupMatcher, r: &mut String, id: u32, index: usize) {
+            let n = &m.nodes[id as usize];
             r.push_str(&format!(
                 r#"
-                    "n{:p}" [
+                    "n{}" [
                         shape = "record"
                         label = ""#,
-                n
+                id
             ));
             let mut edges: String = String::new();
 
-            r.push_str(&format!("<base> [{}] {} Glyphs ", index, n.glyphs.len()));
+            r.push_str(&format!(
+                "<base> [{}] {} Glyphs ",
+                index,
+                glyph_count(m, id)
+            ));
 
             let mut childs: String = String::new();
-            for (i, v) in n.children.iter().enumerate() {
+            for i in 0..n.children_len as usize {
                 r.push_str(&format!(r#" | <f{}> {}"#, i, i));
-                if let Some(z) = v {
-                    recurser(glyphs, &mut childs, z, index + 1);
+                if let Some(z) = m.child(id, i) {
+                    recurser(m, &mut childs, z, index + 1);
                     edges.push_str(&format!(
                         r#"
-                    "n{:p}":f{} -> "n{:p}":base [];
+                    "n{}":f{} -> "n{}":base [];
                     "#,
-                        n, i, z
+                        id, i, z
                     ));
                 }
             }
 
             r.push_str("\"\n                    ];\n");
             // If glyphs were put in the leafs vector, show those here.
-            if !n.leafs.is_empty() {
-                let glyph_string = n
-                    .leafs
+            let leafs = m.node_leafs(id);
+            if !leafs.is_empty() {
+                let glyph_string = leafs
                     .iter()
-                    .map(|g| g.glyph().to_owned())
+                    .map(|g| m.glyphs[*g as usize].glyph().to_owned())
                     .collect::<Vec<String>>()
                     .join(", ")
                     .replace("\\", "\\\\")
                     .replace('"', "\\\"");
                 r.push_str(&format!(
                     r#"
-                        "n{:p}_leafs" [
+                        "n{}_leafs" [
                             shape = "record"
                             label = ""#,
-                    n
+                    id
                 ));
-                r.push_str(&format!("<base> {} Leaf: {}", n.leafs.len(), glyph_string));
+                r.push_str(&format!("<base> {} Leaf: {}", leafs.len(), glyph_string));
                 r.push_str("\"\n                        ");
-                if n.leafs.len() >= 1 && n.children.len() != 0 {
+                if !leafs.is_empty() && n.children_len != 0 {
                     // Leaf here, but there's also children.
                     // Node is hard to reach, there is a glyph that has the same histogram so far
                     // the same but longer.
                     r.push_str("fillcolor = magenta\n                        ");
                     r.push_str("style = filled\n                        ");
-                } else if n.leafs.len() > 1 && n.children.len() == 0 {
+                } else if leafs.len() > 1 && n.children_len == 0 {
                     // Node is always ambiguous, exact same histogram.
                     r.push_str("fillcolor = red\n                        ");
                     r.push_str("style = filled\n                        ");
-                } else if n.leafs.len() == 1 {
+                } else if leafs.len() == 1 {
                     // Node is a leaf and it's by itself, unique histogram.
                     r.push_str("fillcolor = green\n                        ");
                     r.push_str("style = filled\n                        ");
@@ -216,9 +549,36 @@ impl LookupMatcher {
                 r.push_str("\n                        ];\n");
                 edges.push_str(&format!(
                     r#"
-                "n{:p}":base -> "n{:p}_leafs":base [];
+                "n{}":base -> "n{}_leafs":base [];
                 "#,
-                    n, n,
+                    id, id,
+                ));
+            }
+
+            // A tail node holds one glyph with the remainder of its histogram.
+            if n.tail_len != 0 {
+                let glyph_string = m.glyphs[m.tail_glyph(n) as usize]
+                    .glyph()
+                    .replace("\\", "\\\\")
+                    .replace('"', "\\\"");
+                r.push_str(&format!(
+                    r#"
+                        "n{}_tail" [
+                            shape = "record"
+                            label = "<base> Tail {:?}: {}"
+                            fillcolor = green
+                            style = filled
+                        ];
+                "#,
+                    id,
+                    m.node_tail(n),
+                    glyph_string
+                ));
+                edges.push_str(&format!(
+                    r#"
+                "n{}":base -> "n{}_tail":base [];
+                "#,
+                    id, id,
                 ));
             }
 
@@ -226,7 +586,9 @@ impl LookupMatcher {
             r.push_str(&childs);
         }
 
-        recurser(glyphs, &mut res, &self.tree, 0);
+        if !self.nodes.is_empty() {
+            recurser(self, &mut res, 0, 0);
+        }
         res.push_str("}\n");
 
         res
@@ -236,16 +598,29 @@ impl LookupMatcher {
 /// Matcher that returns the longest matching glyph.
 #[derive(Debug, Default, Clone)]
 pub struct LongestGlyphMatcher {
-    matcher: LookupMatcher,
-    lstrip_matcher: LookupMatcher,
+    pub(crate) matcher: LookupMatcher,
+    pub(crate) lstrip_matcher: LookupMatcher,
+    pub(crate) tolerance: Option<Tolerance>,
+    pub(crate) decoder: Decoder,
 }
 
 impl LongestGlyphMatcher {
     /// Create a longest glyph matcher from the provided glyphs.
     pub fn new(glyphs: &[Glyph]) -> Self {
+        Self::prepared(glyphs, false)
+    }
+
+    /// Create a longest glyph matcher with minimal lookup trees, these are much smaller and faster
+    /// to prepare, see [`LookupMatcher::prepare`].
+    pub fn new_minimal(glyphs: &[Glyph]) -> Self {
+        Self::prepared(glyphs, true)
+    }
+
+    fn prepared(glyphs: &[Glyph], minimal: bool) -> Self {
         let mut v: LongestGlyphMatcher = Default::default();
-        v.matcher.prepare(glyphs, false);
-        v.lstrip_matcher.prepare(glyphs, true);
+        let glyphs: Arc<[Glyph]> = glyphs.into();
+        v.matcher.prepare_shared(glyphs.clone(), false, minimal);
+        v.lstrip_matcher.prepare_shared(glyphs, true, minimal);
         v
     }
 
@@ -258,16 +633,71 @@ impl LongestGlyphMatcher {
     pub fn lstrip_matcher(&self) -> &LookupMatcher {
         &self.lstrip_matcher
     }
+
+    /// The tolerance used for matching, None if glyphs have to match exactly.
+    pub fn tolerance(&self) -> Option<Tolerance> {
+        self.tolerance
+    }
+
+    /// Set the tolerance to use for matching, None restores exact matching.
+    pub fn set_tolerance(&mut self, tolerance: Option<Tolerance>) {
+        self.tolerance = tolerance;
+    }
+
+    /// Set the decoder used to find the glyphs in the histograms when scanning.
+    pub fn set_decoder(&mut self, decoder: Decoder) {
+        self.decoder = decoder;
+    }
 }
 
 /// Implementation for the Matcher trait for the LongestGlyphMatcher.
 impl crate::Matcher for LongestGlyphMatcher {
     fn find_match(&self, histogram: &[crate::HistogramType]) -> Option<&Glyph> {
+        if let Some(tolerance) = self.tolerance.as_ref() {
+            return self
+                .matcher
+                .find_match_tolerant(histogram, tolerance)
+                .map(|(g, _)| g);
+        }
         self.matcher.find_match(histogram)
     }
     fn lstrip_find_match(&self, histogram: &[crate::HistogramType]) -> Option<&Glyph> {
+        if let Some(tolerance) = self.tolerance.as_ref() {
+            return self
+                .lstrip_matcher
+                .find_match_tolerant(histogram, tolerance)
+                .map(|(g, _)| g);
+        }
         self.lstrip_matcher.find_match(histogram)
     }
+    fn find_all_matches(&self, histogram: &[crate::HistogramType]) -> Vec<&Glyph> {
+        if let Some(tolerance) = self.tolerance.as_ref() {
+            return self
+                .matcher
+                .find_all_matches_tolerant(histogram, tolerance)
+                .into_iter()
+                .map(|(g, _)| g)
+                .collect();
+        }
+        self.matcher.find_all_matches(histogram)
+    }
+    fn lstrip_find_all_matches(&self, histogram: &[crate::HistogramType]) -> Vec<&Glyph> {
+        if let Some(tolerance) = self.tolerance.as_ref() {
+            return self
+                .lstrip_matcher
+                .find_all_matches_tolerant(histogram, tolerance)
+                .into_iter()
+                .map(|(g, _)| g)
+                .collect();
+        }
+        self.lstrip_matcher.find_all_matches(histogram)
+    }
+    fn is_ambiguous(&self, glyph: &Glyph) -> bool {
+        self.matcher.count_identical(glyph.hist()) > 1
+    }
+    fn decoder(&self) -> Decoder {
+        self.decoder
+    }
 }
 
 #[cfg(test)]
@@ -287,13 +717,14 @@ mod matcher_tests {
     // We ideally want to match the longest token...
 
     use super::*;
+    use crate::Matcher;
     #[test]
     fn test_take_longest() {
         let a = Glyph::new(&[0, 0, 13, 0, 0], &"a");
         let b = Glyph::new(&[0, 0, 13, 0, 0, 0], &"b");
         let z = [a, b];
         let mut matcher: LookupMatcher = Default::default();
-        matcher.prepare(&z, false);
+        matcher.prepare(&z, false, false);
         // In this case, both a and b would match, but b is the longer match so should be taken.
         let res = matcher.find_match(&[0, 0, 13, 0, 0, 0, 1]);
         assert!(res.is_some());
@@ -304,4 +735,276 @@ mod matcher_tests {
         assert!(res.is_some());
         assert_eq!(res.unwrap(), &z[0]);
     }
+
+    #[test]
+    fn test_minimal() {
+        // Random glyphs, with some sharing a prefix or being a prefix of another.
+        let mut state = 0x9e3779b9u32;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let mut z: Vec<Glyph> = vec![];
+        for i in 0..60 {
+            let mut hist = (0..1 + next() % 6)
+                .map(|_| (next() % 4) as u8)
+                .collect::<Vec<u8>>();
+            if i % 3 == 0 && !z.is_empty() {
+                let base = z[next() as usize % z.len()].hist().to_vec();
+                hist = [&base[..], &hist[..(i % 2)]].concat();
+            }
+            z.push(Glyph::new(&hist, &format!("{i}")));
+        }
+        z.push(Glyph::new(&[0, 0, 13, 0, 0], "a"));
+        z.push(Glyph::new(&[0, 0, 13, 0, 0, 0], "b"));
+
+        let mut full: LookupMatcher = Default::default();
+        full.prepare(&z, false, false);
+        let mut minimal: LookupMatcher = Default::default();
+        minimal.prepare(&z, false, true);
+        assert!(minimal.node_count() < full.node_count());
+
+        let tolerance = Tolerance {
+            per_bin: 1,
+            total: 2,
+        };
+        for _ in 0..2000 {
+            let h = (0..next() % 12).map(|_| next() % 5).collect::<Vec<u32>>();
+            assert_eq!(minimal.find_match(&h), full.find_match(&h));
+            assert_eq!(minimal.find_all_matches(&h), full.find_all_matches(&h));
+            assert_eq!(
+                minimal.find_match_tolerant(&h, &tolerance),
+                full.find_match_tolerant(&h, &tolerance)
+            );
+        }
+        for g in z.iter() {
+            for extra in [&[][..], &[0], &[1, 2]] {
+                let h = g
+                    .hist()
+                    .iter()
+                    .map(|v| *v as u32)
+                    .chain(extra.iter().copied())
+                    .collect::<Vec<u32>>();
+                assert_eq!(minimal.find_match(&h), full.find_match(&h));
+                assert_eq!(minimal.find_all_matches(&h), full.find_all_matches(&h));
+            }
+        }
+
+        // The longest match is still taken.
+        let res = minimal.find_match(&[0, 0, 13, 0, 0, 0, 1]);
+        assert_eq!(res.map(|g| g.glyph()), Some("b"));
+        let res = minimal.find_match(&[0, 0, 13, 0, 0, 1]);
+        assert_eq!(res.map(|g| g.glyph()), Some("a"));
+
+        // A single glyph makes the root a tail.
+        let mut single: LookupMatcher = Default::default();
+        single.prepare(&z[..1], false, true);
+        assert_eq!(single.node_count(), 1);
+    }
+
+    #[test]
+    fn test_find_match_index() {
+        let z = [
+            Glyph::new(&[1, 2], "a"),
+            Glyph::new(&[1, 2, 3], "b"),
+            Glyph::new(&[1, 2], "c"),
+            Glyph::new(&[2], "d"),
+        ];
+        let mut matcher: LookupMatcher = Default::default();
+        matcher.prepare(&z, false, false);
+        assert_eq!(matcher.glyphs(), &z);
+        // Root, [1], [1, 2], [1, 2, 3] and [2].
+        assert_eq!(matcher.node_count(), 5);
+        // Children are stored after their parent.
+        for (i, n) in matcher.nodes.iter().enumerate() {
+            assert!(n.children_len == 0 || n.children_start as usize > i);
+        }
+
+        assert_eq!(matcher.find_match_index(&[1, 2, 3, 0]), Some(1));
+        // Identical histograms, the earliest glyph is returned.
+        assert_eq!(matcher.find_match_index(&[1, 2, 0]), Some(0));
+        assert_eq!(matcher.find_match_index(&[2, 0]), Some(3));
+        assert_eq!(matcher.find_match_index(&[3, 0]), None);
+        assert_eq!(matcher.find_match(&[1, 2, 0]), Some(&z[0]));
+
+        // Preparing again replaces the previous glyphs.
+        matcher.prepare(&z[3..], false, false);
+        assert_eq!(matcher.find_match_index(&[2, 0]), Some(0));
+        assert_eq!(matcher.node_count(), 2);
+    }
+
+    #[test]
+    fn test_empty_matcher() {
+        use crate::Matcher;
+        for minimal in [false, true] {
+            let mut matcher: LookupMatcher = Default::default();
+            matcher.prepare(&[], false, minimal);
+            assert_eq!(matcher.find_match(&[1, 2, 3]), None);
+            assert!(matcher.find_all_matches(&[1, 2, 3]).is_empty());
+        }
+
+        // Without lstrip histograms the lstrip matcher is empty.
+        let mut space = Glyph::new(&[0, 0, 0], " ");
+        space.set_ignore_on_lstrip(true);
+        let mut dot = Glyph::new(&[1], ".");
+        dot.set_ignore_on_lstrip(true);
+        let matcher = LongestGlyphMatcher::new(&[space, dot]);
+        assert_eq!(matcher.lstrip_find_match(&[1, 2, 3]), None);
+        assert_eq!(matcher.find_match(&[1, 0]).map(|g| g.glyph()), Some("."));
+    }
+
+    #[test]
+    fn test_find_all_matches() {
+        let a = Glyph::new(&[0, 2, 3, 3], "a");
+        let b = Glyph::new(&[0, 2, 3], "b");
+        let c = Glyph::new(&[3, 4, 5, 6], "c");
+        let d = Glyph::new(&[0, 2, 3], "d");
+        let z = [a, b, c, d];
+        let mut matcher: LookupMatcher = Default::default();
+        matcher.prepare(&z, false, false);
+
+        let res = matcher.find_all_matches(&[0, 2, 3, 3, 4, 5, 6]);
+        assert_eq!(res, vec![&z[1], &z[3], &z[0]]);
+
+        // The histogram may end exactly at the end of the glyph.
+        let res = matcher.find_all_matches(&[0, 2, 3]);
+        assert_eq!(res, vec![&z[1], &z[3]]);
+
+        let res = matcher.find_all_matches(&[0, 2, 4]);
+        assert!(res.is_empty());
+
+        // Only b and d share their histogram, also in the minimal tree where c is a tail.
+        for minimal in [false, true] {
+            let mut matcher: LookupMatcher = Default::default();
+            matcher.prepare(&z, false, minimal);
+            let counts = z
+                .iter()
+                .map(|g| matcher.count_identical(g.hist()))
+                .collect::<Vec<_>>();
+            assert_eq!(counts, vec![1, 2, 1, 2]);
+            assert_eq!(matcher.count_identical(&[3, 4]), 0);
+            assert_eq!(matcher.count_identical(&[3, 4, 5, 6, 7]), 0);
+        }
+    }
+
+    #[test]
+    fn test_find_match_tolerant() {
+        let a = Glyph::new(&[1, 3, 5, 3, 1], "a");
+        let b = Glyph::new(&[2, 2, 2, 2], "b");
+        let c = Glyph::new(&[1, 3, 5, 3, 1, 7], "c");
+        let z = [a, b, c];
+        let mut matcher: LookupMatcher = Default::default();
+        matcher.prepare(&z, false, false);
+
+        // One bin is off by one, exact matching fails.
+        let hist = [1, 4, 5, 3, 1, 0, 0];
+        assert!(matcher.find_match(&hist).is_none());
+
+        // With a tolerance we find a, with the error.
+        let tolerance = Tolerance {
+            per_bin: 1,
+            total: 2,
+        };
+        let res = matcher.find_match_tolerant(&hist, &tolerance);
+        assert_eq!(res, Some((&z[0], 1)));
+
+        // The total budget is exceeded if more bins are off.
+        let hist = [2, 4, 4, 3, 1, 0, 0];
+        assert!(matcher.find_match_tolerant(&hist, &tolerance).is_none());
+
+        // A single bin that is off by more than the per bin tolerance is rejected.
+        let hist = [1, 3, 7, 3, 1, 0, 0];
+        let tolerance = Tolerance {
+            per_bin: 1,
+            total: 10,
+        };
+        assert!(matcher.find_match_tolerant(&hist, &tolerance).is_none());
+
+        // The longest glyph within the tolerance wins, c is off by one in the last bin while a
+        // matches exactly.
+        let hist = [1, 3, 5, 3, 1, 6, 0];
+        assert_eq!(
+            matcher.find_match_tolerant(&hist, &tolerance),
+            Some((&z[2], 1))
+        );
+
+        // Both match exactly, the longest glyph wins.
+        let hist = [1, 3, 5, 3, 1, 7, 0];
+        assert_eq!(
+            matcher.find_match_tolerant(&hist, &tolerance),
+            Some((&z[2], 0))
+        );
+
+        // All glyphs within the tolerance, shortest first, in both modes.
+        let hist = [1, 3, 5, 3, 1, 6, 0];
+        let mut minimal: LookupMatcher = Default::default();
+        minimal.prepare(&z, false, true);
+        for m in [&matcher, &minimal] {
+            assert_eq!(
+                m.find_all_matches_tolerant(&hist, &tolerance),
+                vec![(&z[0], 0), (&z[2], 1)]
+            );
+        }
+        let mut longest = LongestGlyphMatcher::new(&z);
+        assert_eq!(longest.find_all_matches(&hist), vec![&z[0]]);
+        longest.set_tolerance(Some(tolerance));
+        assert_eq!(longest.find_all_matches(&hist), vec![&z[0], &z[2]]);
+        assert_eq!(longest.find_match(&hist), Some(&z[2]));
+    }
+
+    #[test]
+    fn test_find_match_tolerant_prefix() {
+        // A short glyph that is the prefix of a longer one must not win just because it matches
+        // without error.
+        let z = [
+            Glyph::new(&[5], "short"),
+            Glyph::new(&[5, 1, 5, 1, 5], "long"),
+            Glyph::new(&[5, 1, 5, 1, 4], "other"),
+        ];
+        let tolerance = Tolerance {
+            per_bin: 1,
+            total: 1,
+        };
+        for minimal in [false, true] {
+            let mut matcher: LookupMatcher = Default::default();
+            matcher.prepare(&z, false, minimal);
+            assert_eq!(
+                matcher.find_match_tolerant(&[5, 1, 5, 1, 6], &tolerance),
+                Some((&z[1], 1))
+            );
+            // Of the glyphs with the same length, the one with the lowest error wins.
+            assert_eq!(
+                matcher.find_match_tolerant(&[5, 1, 5, 1, 4], &tolerance),
+                Some((&z[2], 0))
+            );
+        }
+    }
+
+    #[test]
+    fn test_longest_minimal() {
+        let z = [
+            Glyph::new(&[0, 2, 3, 3], "a"),
+            Glyph::new(&[0, 2, 3], "b"),
+            Glyph::new(&[3, 4, 5, 6], "c"),
+        ];
+        // Minimal trees are opt in, both give the same matches.
+        let full = LongestGlyphMatcher::new(&z);
+        let minimal = LongestGlyphMatcher::new_minimal(&z);
+        assert!(full.matcher().tails.is_empty());
+        assert!(minimal.matcher().node_count() < full.matcher().node_count());
+        for hist in [
+            &[0, 2, 3, 3, 1][..],
+            &[0, 2, 3, 4],
+            &[3, 4, 5, 6, 0],
+            &[3, 4, 5],
+        ] {
+            assert_eq!(minimal.find_match(hist), full.find_match(hist));
+            assert_eq!(
+                minimal.lstrip_find_match(hist),
+                full.lstrip_find_match(hist)
+            );
+        }
+    }
 }
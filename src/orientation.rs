@@ -0,0 +1,142 @@
+//! Scanning text that isn't written from left to right.
+//!
+//! The image is viewed through an [`OrientedView`] in which the text reads from left to right,
+//! such that the same glyph sets can be used. Match locations are mapped back to the original
+//! image afterwards.
+
+use image::GenericImageView;
+
+use crate::Rect;
+
+/// Direction in which the text in the image is read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    /// Normal text, read from left to right.
+    #[default]
+    LeftToRight,
+    /// Text rotated 90 degrees clockwise, read from top to bottom with the tops of the letters
+    /// facing right.
+    TopToBottom,
+    /// Text rotated 90 degrees counter clockwise, read from bottom to top with the tops of the
+    /// letters facing left.
+    BottomToTop,
+    /// Text that is upside down, read from right to left.
+    Rotated180,
+}
+
+impl Orientation {
+    /// Dimensions of the view of an image with these dimensions.
+    pub fn view_dimensions(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        match self {
+            Orientation::LeftToRight | Orientation::Rotated180 => (width, height),
+            Orientation::TopToBottom | Orientation::BottomToTop => (height, width),
+        }
+    }
+
+    /// Map a pixel in the view to the pixel in the image with these dimensions.
+    pub fn to_image(&self, (width, height): (u32, u32), x: u32, y: u32) -> (u32, u32) {
+        match self {
+            Orientation::LeftToRight => (x, y),
+            Orientation::TopToBottom => (width - 1 - y, x),
+            Orientation::BottomToTop => (y, height - 1 - x),
+            Orientation::Rotated180 => (width - 1 - x, height - 1 - y),
+        }
+    }
+
+    /// Map a rectangle in the view to the rectangle it covers in the image with these dimensions.
+    pub fn rect_to_image(&self, dimensions: (u32, u32), rect: &Rect) -> Rect {
+        let (x0, y0) = self.to_image(dimensions, rect.left(), rect.bottom());
+        let (x1, y1) = self.to_image(dimensions, rect.right(), rect.top());
+        Rect {
+            x: x0.min(x1),
+            y: y0.min(y1),
+            w: x0.abs_diff(x1),
+            h: y0.abs_diff(y1),
+        }
+    }
+}
+
+/// View of an image in which text of the orientation reads from left to right.
+pub struct OrientedView<'a, I: GenericImageView> {
+    image: &'a I,
+    orientation: Orientation,
+}
+
+impl<'a, I: GenericImageView> OrientedView<'a, I> {
+    /// Create a view on the image for text in this orientation.
+    pub fn new(image: &'a I, orientation: Orientation) -> Self {
+        OrientedView { image, orientation }
+    }
+}
+
+impl<I: GenericImageView> GenericImageView for OrientedView<'_, I> {
+    type Pixel = I::Pixel;
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.orientation.view_dimensions(self.image.dimensions())
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Self::Pixel {
+        let (x, y) = self.orientation.to_image(self.image.dimensions(), x, y);
+        self.image.get_pixel(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_oriented_view() {
+        let mut image = RgbImage::new(4, 3);
+        *image.get_pixel_mut(1, 0) = Rgb([255, 0, 0]);
+        let dimensions = image.dimensions();
+        let marked = Rect {
+            x: 1,
+            y: 0,
+            w: 0,
+            h: 0,
+        };
+
+        for orientation in [
+            Orientation::LeftToRight,
+            Orientation::TopToBottom,
+            Orientation::BottomToTop,
+            Orientation::Rotated180,
+        ] {
+            let view = OrientedView::new(&image, orientation);
+            assert_eq!(view.dimensions(), orientation.view_dimensions(dimensions));
+            let mut found = vec![];
+            for (x, y, p) in view.pixels() {
+                let (image_x, image_y) = orientation.to_image(dimensions, x, y);
+                assert_eq!(p, *image.get_pixel(image_x, image_y));
+                if p == Rgb([255, 0, 0]) {
+                    found.push((x, y));
+                }
+            }
+            assert_eq!(found.len(), 1);
+            let (x, y) = found[0];
+            let rect = Rect { x, y, w: 0, h: 0 };
+            assert_eq!(orientation.rect_to_image(dimensions, &rect), marked);
+        }
+
+        // The rectangle covering the whole view covers the whole image.
+        let view = Rect {
+            x: 0,
+            y: 0,
+            w: 2,
+            h: 3,
+        };
+        let full = Rect {
+            x: 0,
+            y: 0,
+            w: 3,
+            h: 2,
+        };
+        assert_eq!(
+            Orientation::TopToBottom.rect_to_image(dimensions, &view),
+            full
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Configurable scanning of images.
+//!
+//! A [`Scanner`] owns the matcher and the labels together with the [`ScanOptions`] that configure
+//! the scan, such that it can be set up once and used for many images. The
+//! [`moving_windowed_histogram`](crate::moving_windowed_histogram) function performs the same scan
+//! with the default options.
+
+use std::collections::VecDeque;
+
+use image::{GenericImageView, Pixel};
+
+use crate::{
+    check_unique_labels, decide_on_matches, finalize_considerations, window_matches,
+    ColorClassifier, ColorLabel, Match2D, Matcher, Result, WindowHistogramIterator,
+};
+
+/// How matches that overlap each other are resolved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OverlapPolicy {
+    /// Of overlapping matches only the one that covers the most pixels is kept.
+    #[default]
+    KeepBest,
+    /// All matches are kept, the same text is usually found in multiple window positions.
+    KeepAll,
+}
+
+/// Options that configure a scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Height of the window that slides over the image, usually the line height of the glyph set.
+    pub window_size: u32,
+    /// Matches with fewer tokens are discarded.
+    pub min_tokens: usize,
+    /// Matches of which the glyphs cover fewer pixels are discarded.
+    pub min_pixels: u32,
+    /// Number of rows the window moves between the positions at which it is matched, a step of 0
+    /// is treated as 1.
+    pub step: u32,
+    /// How overlapping matches are resolved.
+    pub overlap: OverlapPolicy,
+    /// Only matches of the labels with these values are kept, all labels are kept if None.
+    pub label_filter: Option<Vec<u32>>,
+}
+
+impl ScanOptions {
+    /// Options for a window of the provided height, that match every window position and keep all
+    /// matches that don't overlap a better one.
+    pub fn new(window_size: u32) -> Self {
+        ScanOptions {
+            window_size,
+            min_tokens: 0,
+            min_pixels: 0,
+            step: 1,
+            overlap: Default::default(),
+            label_filter: None,
+        }
+    }
+
+    /// Set the minimum number of tokens in a match.
+    pub fn with_min_tokens(mut self, min_tokens: usize) -> Self {
+        self.min_tokens = min_tokens;
+        self
+    }
+
+    /// Set the minimum number of pixels covered by the glyphs of a match.
+    pub fn with_min_pixels(mut self, min_pixels: u32) -> Self {
+        self.min_pixels = min_pixels;
+        self
+    }
+
+    /// Set the number of rows between the matched window positions.
+    pub fn with_step(mut self, step: u32) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Set how overlapping matches are resolved.
+    pub fn with_overlap(mut self, overlap: OverlapPolicy) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Only keep the matches of the labels with these values.
+    pub fn with_label_filter(mut self, labels: &[u32]) -> Self {
+        self.label_filter = Some(labels.to_vec());
+        self
+    }
+
+    /// Whether the match passes the minimums and the label filter.
+    fn accepts(&self, m: &Match2D) -> bool {
+        let label_allowed = match (&self.label_filter, m.tokens.first()) {
+            (Some(filter), Some(first)) => filter.contains(&first.label),
+            _ => true,
+        };
+        label_allowed
+            && m.tokens.len() >= self.min_tokens
+            && m.confidence.matched_pixels >= self.min_pixels
+    }
+}
+
+/// Scanner that owns the matcher and the labels it scans images with.
+#[derive(Debug, Clone)]
+pub struct Scanner<M: Matcher> {
+    matcher: M,
+    labels: Vec<ColorLabel>,
+    classifier: ColorClassifier,
+    options: ScanOptions,
+}
+
+impl<M: Matcher> Scanner<M> {
+    /// Create a scanner, fails if the colors of the labels are not unique or there are too many
+    /// labels. The pixels of all scans are classified with the same classifier.
+    pub fn new(matcher: M, labels: &[ColorLabel], options: ScanOptions) -> Result<Self> {
+        check_unique_labels(labels)?;
+        Ok(Scanner {
+            matcher,
+            labels: labels.to_vec(),
+            classifier: ColorClassifier::new(labels)?,
+            options,
+        })
+    }
+
+    /// The matcher used to find the glyphs.
+    pub fn matcher(&self) -> &M {
+        &self.matcher
+    }
+
+    /// The labels of the colors that are scanned for.
+    pub fn labels(&self) -> &[ColorLabel] {
+        &self.labels
+    }
+
+    /// The options used for scanning.
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Change the options used for scanning.
+    pub fn set_options(&mut self, options: ScanOptions) {
+        self.options = options;
+    }
+
+    /// Scan the image, fails if the window is taller than the image.
+    ///
+    /// Each match holds the single reading of the matcher, other readings of ambiguous matches can
+    /// be obtained with [`language::n_best`](crate::language::n_best), the image, the scanner's
+    /// labels and the scanner's matcher.
+    pub fn scan<I: GenericImageView>(&self, image: &I) -> Result<Vec<Match2D<'_>>>
+    where
+        <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+    {
+        scan(
+            image,
+            &self.matcher,
+            &self.labels,
+            &self.classifier,
+            &self.options,
+        )
+    }
+}
+
+/// Slide the window over the image and match it at the positions and with the filters of the
+/// options. The classifier must be built for the labels.
+pub(crate) fn scan<'a, I: GenericImageView>(
+    image: &I,
+    matcher: &'a dyn Matcher,
+    labels: &[ColorLabel],
+    classifier: &ColorClassifier,
+    options: &ScanOptions,
+) -> Result<Vec<Match2D<'a>>>
+where
+    <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
+{
+    let step = options.step.max(1);
+    let mut res_final: Vec<Match2D<'a>> = Vec::new();
+
+    // Container for results under consideration, we check matches against overlap in this window
+    // and keep the parts that are the best matches.
+    let mut res_consider: VecDeque<Match2D<'a>> = VecDeque::new();
+
+    let mut iterable =
+        WindowHistogramIterator::with_classifier(image, labels, classifier, options.window_size)?;
+    let mut more_to_come = true;
+    while more_to_come {
+        let y = iterable.y();
+        if y % step == 0 {
+            let histograms = iterable.histograms();
+            let rows = |index: usize, x, width| histograms[index].row_counts(x, width);
+            let mut matches_2d = window_matches(y, options.window_size, histograms, matcher, &rows);
+            // Discard matches before deciding, such that they can't displace the ones we keep.
+            matches_2d.retain(|m| options.accepts(m));
+            match options.overlap {
+                OverlapPolicy::KeepBest => {
+                    decide_on_matches(matches_2d, &mut res_consider);
+                    finalize_considerations(y, &mut res_consider, &mut res_final);
+                }
+                OverlapPolicy::KeepAll => res_final.extend(matches_2d),
+            }
+        }
+        more_to_come = iterable.advance();
+    }
+
+    res_final.extend(res_consider.drain(..));
+
+    Ok(res_final)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::LongestGlyphMatcher;
+    use crate::test_util::test_alphabet::{render_standard_color, standard_glyph_set, Alphabet};
+    use crate::{moving_windowed_histogram, Decoder, Error};
+    use image::Rgb;
+
+    #[test]
+    fn test_scanner() {
+        let glyph_set = standard_glyph_set();
+        let white = Rgb::<u8>([255, 255, 255]);
+        let red = Rgb::<u8>([255, 0, 0]);
+        let labels = vec![(white.into(), 0), (red.into(), 1)];
+        let mut image = image::RgbImage::new(100, 40);
+        render_standard_color(&mut image, 5, 3, "deeb", white);
+        render_standard_color(&mut image, 50, 6, "c", white);
+        render_standard_color(&mut image, 40, 26, "wacb", red);
+
+        let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        let options = ScanOptions::new(glyph_set.line_height);
+        let scanner = Scanner::new(matcher.clone(), &labels, options.clone()).unwrap();
+        let texts = |options: ScanOptions| {
+            let mut scanner = scanner.clone();
+            scanner.set_options(options);
+            let mut res = scanner
+                .scan(&image)
+                .unwrap()
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>();
+            res.sort();
+            res
+        };
+
+        // The default options are identical to the plain function.
+        let expected = moving_windowed_histogram(&image, glyph_set.line_height, &matcher, &labels)
+            .unwrap()
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            scanner
+                .scan(&image)
+                .unwrap()
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(texts(options.clone()), vec!["c", "deeb", "wacb"]);
+
+        assert_eq!(
+            texts(options.clone().with_min_tokens(2)),
+            vec!["deeb", "wacb"]
+        );
+        let c_pixels = glyph_set
+            .entries
+            .iter()
+            .find(|g| g.glyph() == "c")
+            .unwrap()
+            .total();
+        assert_eq!(
+            texts(options.clone().with_min_pixels(c_pixels + 1)),
+            vec!["deeb", "wacb"]
+        );
+        assert_eq!(texts(options.clone().with_label_filter(&[1])), vec!["wacb"]);
+
+        // Without deciding on overlaps, the partial matches in the windows that are not aligned
+        // with the text are kept as well.
+        let all = texts(options.clone().with_overlap(OverlapPolicy::KeepAll));
+        assert!(all.len() > expected.len());
+        assert!(expected.iter().all(|t| all.contains(t)));
+
+        // With a step, only the windows that are at a multiple of it are matched. The deeb at row
+        // 3 is missed, the others are still found in a window at an even row.
+        assert_eq!(texts(options.clone().with_step(2)), vec!["c", "wacb"]);
+        assert_eq!(texts(options.clone().with_step(0)), texts(options.clone()));
+
+        assert!(matches!(
+            Scanner::new(matcher, &[(white.into(), 0), (white.into(), 1)], options),
+            Err(Error::DuplicateColorLabel(_))
+        ));
+    }
+
+    #[test]
+    fn test_scanner_decoder() {
+        // The example from the comment above bin_glyph_matcher, a is [2, 3, 3], b is [2, 3] and
+        // c is [3, 4]. The text bc starts with a, after which the greedy decoder is stuck.
+        let alphabet = Alphabet::new(4)
+            .with_spacing(0)
+            .with_letter('a', "xxx\nxxx\n.xx\n...")
+            .with_letter('b', "xx\nxx\n.x\n..")
+            .with_letter('c', "xx\nxx\nxx\n.x");
+        let glyph_set = alphabet.glyph_set();
+        let white = Rgb::<u8>([255, 255, 255]);
+        let labels = vec![(white.into(), 0)];
+        let mut image = image::RgbImage::new(20, 6);
+        alphabet.render(&mut image, 2, 1, "bc", white);
+
+        let mut matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+        let texts = |matcher: &LongestGlyphMatcher| {
+            let scanner = Scanner::new(matcher.clone(), &labels, ScanOptions::new(4)).unwrap();
+            scanner
+                .scan(&image)
+                .unwrap()
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matcher.decoder(), Decoder::Greedy);
+        assert!(!texts(&matcher).contains(&"bc".to_owned()));
+
+        matcher.set_decoder(Decoder::Search);
+        assert_eq!(texts(&matcher), vec!["bc"]);
+        // The other scans use the decoder of the matcher as well.
+        let found = moving_windowed_histogram(&image, 4, &matcher, &labels).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].to_string(), "bc");
+    }
+}
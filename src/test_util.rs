@@ -15,27 +15,9 @@ pub fn apply_stamp(image: &mut RgbImage, x: u32, y: u32, stamp: &RgbaImage) {
 
 pub mod test_alphabet {
     use super::*;
-
-    /// Helper function to convert letters from the ascii format below to an rgba image.
-    fn letter_to_rgba(v: &str, color: Rgba<u8>) -> RgbaImage {
-        let lines = v
-            .lines()
-            .map(|x| x.trim())
-            .filter(|x| !x.is_empty())
-            .collect::<Vec<&str>>();
-        let mut image = RgbaImage::new(lines[0].len() as u32, lines.len() as u32);
-        for (y, row) in lines.iter().enumerate() {
-            for (x, v) in (**row).chars().enumerate() {
-                match v {
-                    'x' => {
-                        *image.get_pixel_mut(x as u32, y as u32) = color;
-                    }
-                    _ => {}
-                }
-            }
-        }
-        image
-    }
+    use crate::glyphs::{Glyph, GlyphSet};
+    use std::collections::BTreeMap;
+    use std::sync::OnceLock;
 
     const LETTER_HEIGHT: u32 = 7;
     // Letters shall be 7 high. Because that's odd and allows for center lines.
@@ -88,35 +70,30 @@ pub mod test_alphabet {
         ..x.x.x.x..
         ..x.x.x.x..
         ...x...x...";
-    const SPACE: &'static str = "
-        .....
-        .....
-        .....
-        .....
-        .....
-        .....
-        .....";
+    /// The letter of the standard alphabet in white.
+    fn white_stamp(letter: char) -> RgbaImage {
+        Alphabet::standard()
+            .stamp(letter, Rgb([255u8, 255, 255]))
+            .expect("letter is in the standard alphabet")
+    }
 
     pub fn white_a() -> RgbaImage {
-        letter_to_rgba(&A, Rgba([255u8, 255, 255, 255]))
+        white_stamp('a')
     }
     pub fn white_b() -> RgbaImage {
-        letter_to_rgba(&B, Rgba([255u8, 255, 255, 255]))
+        white_stamp('b')
     }
     pub fn white_c() -> RgbaImage {
-        letter_to_rgba(&C, Rgba([255u8, 255, 255, 255]))
+        white_stamp('c')
     }
     pub fn white_d() -> RgbaImage {
-        letter_to_rgba(&D, Rgba([255u8, 255, 255, 255]))
+        white_stamp('d')
     }
     pub fn white_e() -> RgbaImage {
-        letter_to_rgba(&E, Rgba([255u8, 255, 255, 255]))
+        white_stamp('e')
     }
     pub fn white_w() -> RgbaImage {
-        letter_to_rgba(&W, Rgba([255u8, 255, 255, 255]))
-    }
-    pub fn white_space() -> RgbaImage {
-        letter_to_rgba(&SPACE, Rgba([255u8, 255, 255, 255]))
+        white_stamp('w')
     }
 
     pub fn render_standard(image: &mut RgbImage, x: u32, y: u32, text: &str) -> u32 {
@@ -130,49 +107,637 @@ pub mod test_alphabet {
         text: &str,
         color: Rgb<u8>,
     ) -> u32 {
-        let lsb = 1;
-        let rsb = 1;
-        let mut x = x + lsb;
-
-        let r = color.channels()[0];
-        let g = color.channels()[1];
-        let b = color.channels()[2];
-        for c in text.chars() {
-            let l = match c {
-                'a' => letter_to_rgba(&A, Rgba([r, g, b, 255])),
-                'b' => letter_to_rgba(&B, Rgba([r, g, b, 255])),
-                'c' => letter_to_rgba(&C, Rgba([r, g, b, 255])),
-                'd' => letter_to_rgba(&D, Rgba([r, g, b, 255])),
-                'e' => letter_to_rgba(&E, Rgba([r, g, b, 255])),
-                'w' => letter_to_rgba(&W, Rgba([r, g, b, 255])),
-                ' ' => white_space(),
-                _ => {
-                    panic!("letter does not exist in alphabet")
+        Alphabet::standard().render(image, x, y, text, color)
+    }
+
+    /// All letters of the standard alphabet rendered in white.
+    pub fn render_standard_alphabet() -> RgbImage {
+        let alphabet = Alphabet::standard();
+        alphabet.render_text(&alphabet.letters(), Rgb([255u8, 255, 255]))
+    }
+
+    /// All letters of the standard alphabet rendered in white, with the letters in order.
+    pub fn standard_alphabet() -> (RgbImage, String) {
+        (render_standard_alphabet(), Alphabet::standard().letters())
+    }
+
+    /// Width of the space in the standard alphabet.
+    pub const SPACE_WIDTH: u32 = 5;
+
+    /// Glyph set of the standard alphabet, with the glyphs named after their letters.
+    pub fn standard_glyph_set() -> GlyphSet {
+        static GLYPH_SET: OnceLock<GlyphSet> = OnceLock::new();
+        GLYPH_SET
+            .get_or_init(|| Alphabet::standard().glyph_set())
+            .clone()
+    }
+
+    /// An alphabet of ascii-art letters, defined at runtime.
+    ///
+    /// Letters are drawn with an 'x' for each pixel with ink and a '.' for each empty pixel, one
+    /// line per row, whitespace around the lines is ignored. All letters must have the same height.
+    /// Text is rendered with the spacing before the first letter and after each letter, a space
+    /// leaves a gap of the space width.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Alphabet {
+        height: u32,
+        spacing: u32,
+        space_width: u32,
+        letters: BTreeMap<char, Vec<Vec<bool>>>,
+    }
+
+    impl Alphabet {
+        /// Create an empty alphabet of letters with this height, with a spacing of one pixel and
+        /// a space as wide as the height.
+        pub fn new(height: u32) -> Self {
+            Alphabet {
+                height,
+                spacing: 1,
+                space_width: height,
+                letters: Default::default(),
+            }
+        }
+
+        /// The alphabet with the letters a, b, c, d, e and w, used throughout the tests. It is
+        /// built once.
+        pub fn standard() -> &'static Self {
+            static STANDARD: OnceLock<Alphabet> = OnceLock::new();
+            STANDARD.get_or_init(|| {
+                Alphabet::new(LETTER_HEIGHT)
+                    .with_space_width(SPACE_WIDTH)
+                    .with_letter('a', A)
+                    .with_letter('b', B)
+                    .with_letter('c', C)
+                    .with_letter('d', D)
+                    .with_letter('e', E)
+                    .with_letter('w', W)
+            })
+        }
+
+        /// Set the number of empty columns after each letter.
+        pub fn with_spacing(mut self, spacing: u32) -> Self {
+            self.spacing = spacing;
+            self
+        }
+
+        /// Set the width of the gap left by a space.
+        pub fn with_space_width(mut self, space_width: u32) -> Self {
+            self.space_width = space_width;
+            self
+        }
+
+        /// Add a letter in the ascii-art format, panics if the art is malformed.
+        pub fn with_letter(mut self, letter: char, art: &str) -> Self {
+            assert!(!letter.is_whitespace(), "whitespace can not be a letter");
+            let rows = art
+                .lines()
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .map(|row| {
+                    row.chars()
+                        .map(|v| match v {
+                            'x' => true,
+                            '.' => false,
+                            _ => panic!("letter {letter:?} holds {v:?}, only 'x' and '.' allowed"),
+                        })
+                        .collect::<Vec<bool>>()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                rows.len() as u32,
+                self.height,
+                "letter {letter:?} has the wrong height"
+            );
+            assert!(
+                rows.iter().all(|r| r.len() == rows[0].len()),
+                "rows of letter {letter:?} differ in width"
+            );
+            self.letters.insert(letter, rows);
+            self
+        }
+
+        /// Height of the letters.
+        pub fn height(&self) -> u32 {
+            self.height
+        }
+
+        /// The letters in the alphabet, in order.
+        pub fn letters(&self) -> String {
+            self.letters.keys().collect()
+        }
+
+        /// The letter as an rgba image, transparent where there is no ink.
+        pub fn stamp(&self, letter: char, color: Rgb<u8>) -> Option<RgbaImage> {
+            let rows = self.letters.get(&letter)?;
+            let [r, g, b] = color.0;
+            let mut image = RgbaImage::new(rows[0].len() as u32, self.height);
+            for (y, row) in rows.iter().enumerate() {
+                for (x, ink) in row.iter().enumerate() {
+                    if *ink {
+                        *image.get_pixel_mut(x as u32, y as u32) = Rgba([r, g, b, 255]);
+                    }
+                }
+            }
+            Some(image)
+        }
+
+        fn letter_width(&self, c: char) -> u32 {
+            if c == ' ' {
+                return self.space_width;
+            }
+            self.letters
+                .get(&c)
+                .map(|rows| rows[0].len() as u32)
+                .unwrap_or_else(|| panic!("letter {c:?} does not exist in alphabet"))
+        }
+
+        /// Width of the text when rendered, panics on letters that are not in the alphabet.
+        pub fn width(&self, text: &str) -> u32 {
+            self.spacing
+                + text
+                    .chars()
+                    .map(|c| self.letter_width(c) + self.spacing)
+                    .sum::<u32>()
+        }
+
+        /// Render the text with its top left at x, y, returns the x position after the text.
+        /// Panics on letters that are not in the alphabet.
+        pub fn render(
+            &self,
+            image: &mut RgbImage,
+            x: u32,
+            y: u32,
+            text: &str,
+            color: Rgb<u8>,
+        ) -> u32 {
+            let mut x = x + self.spacing;
+            for c in text.chars() {
+                if let Some(stamp) = self.stamp(c, color) {
+                    apply_stamp(image, x, y, &stamp);
                 }
+                x += self.letter_width(c) + self.spacing;
+            }
+            x
+        }
+
+        /// Render the text on a black image that fits it exactly.
+        pub fn render_text(&self, text: &str, color: Rgb<u8>) -> RgbImage {
+            let mut image = RgbImage::new(self.width(text), self.height);
+            self.render(&mut image, 0, 0, text, color);
+            image
+        }
+
+        /// The glyph set that matches the letters, with the glyphs named after their letters. The
+        /// space is not part of it.
+        pub fn glyph_set(&self) -> GlyphSet {
+            let mut glyph_set = GlyphSet {
+                line_height: self.height,
+                ..Default::default()
             };
-            apply_stamp(image, x, y, &l);
-            x += l.width() + rsb;
+            for (letter, rows) in self.letters.iter() {
+                let hist = (0..rows[0].len())
+                    .map(|x| rows.iter().filter(|r| r[x]).count() as u8)
+                    .collect::<Vec<u8>>();
+                glyph_set
+                    .entries
+                    .push(Glyph::new(&hist, &letter.to_string()));
+            }
+            glyph_set.prepare();
+            glyph_set
+        }
+
+        /// All letters rendered in white, with the glyph set that matches them.
+        pub fn render_alphabet(&self) -> (RgbImage, GlyphSet) {
+            (
+                self.render_text(&self.letters(), Rgb([255, 255, 255])),
+                self.glyph_set(),
+            )
         }
-        x
     }
+}
 
-    const TEXT_STANDARD_ALPHABET: &'static str = "abcdew";
-    pub fn render_standard_alphabet() -> RgbImage {
-        let mut image = RgbImage::new(512, 512);
-        let width = render_standard(&mut image, 0, 0, &TEXT_STANDARD_ALPHABET);
-        let mut image = RgbImage::new(width, LETTER_HEIGHT);
-        render_standard(&mut image, 0, 0, &TEXT_STANDARD_ALPHABET);
-        image
+/// Deterministic screenshots with known text, for benchmarks and accuracy regression tests.
+pub mod synthetic {
+    use super::*;
+    use crate::glyphs::GlyphSet;
+    use crate::training::{AnnotatedImage, Collection};
+    use crate::{ColorLabel, Rect};
+    use ab_glyph::{Font, PxScale};
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    /// Something that draws text, together with the glyph set that recognises it.
+    pub trait SyntheticRenderer {
+        /// The glyph set that matches the rendered text.
+        fn glyph_set(&self) -> &GlyphSet;
+        /// Width of the text when rendered.
+        fn width(&self, text: &str) -> u32;
+        /// Draw the text with its top left at x, y, returns the x position after the text.
+        fn render(&self, image: &mut RgbImage, x: u32, y: u32, text: &str, color: Rgb<u8>) -> u32;
+    }
+
+    /// Renders with an ascii-art alphabet.
+    pub struct AlphabetRenderer {
+        alphabet: test_alphabet::Alphabet,
+        glyph_set: GlyphSet,
+    }
+
+    impl AlphabetRenderer {
+        pub fn new(alphabet: test_alphabet::Alphabet) -> Self {
+            AlphabetRenderer {
+                glyph_set: alphabet.glyph_set(),
+                alphabet,
+            }
+        }
+
+        /// Renderer of the standard test alphabet.
+        pub fn standard() -> Self {
+            Self::new(test_alphabet::Alphabet::standard().clone())
+        }
+    }
+
+    impl SyntheticRenderer for AlphabetRenderer {
+        fn glyph_set(&self) -> &GlyphSet {
+            &self.glyph_set
+        }
+        fn width(&self, text: &str) -> u32 {
+            self.alphabet.width(text)
+        }
+        fn render(&self, image: &mut RgbImage, x: u32, y: u32, text: &str, color: Rgb<u8>) -> u32 {
+            self.alphabet.render(image, x, y, text, color)
+        }
+    }
+
+    /// Renders with a font, the glyph set is created with [`crate::image_support::glyph_set_from_font`].
+    ///
+    /// Characters are placed one by one at whole pixels and only the pixels that reach the
+    /// threshold are drawn, such that the rendering matches the glyph set exactly.
+    pub struct FontRenderer<F: Font> {
+        font: F,
+        scale: PxScale,
+        threshold: u8,
+        glyph_set: GlyphSet,
+    }
+
+    impl<F: Font> FontRenderer<F> {
+        /// Create the renderer for the characters in the charset.
+        pub fn new(font: F, size: f32, charset: &str, threshold: u8) -> Self {
+            let glyph_set = crate::image_support::glyph_set_from_font(
+                &font,
+                size,
+                charset,
+                threshold,
+                &Default::default(),
+            );
+            FontRenderer {
+                font,
+                scale: PxScale::from(size),
+                threshold,
+                glyph_set,
+            }
+        }
+    }
+
+    impl<F: Font> SyntheticRenderer for FontRenderer<F> {
+        fn glyph_set(&self) -> &GlyphSet {
+            &self.glyph_set
+        }
+        fn width(&self, text: &str) -> u32 {
+            text.chars()
+                .map(|c| glyph_width(&self.glyph_set, &c.to_string()))
+                .sum()
+        }
+        fn render(&self, image: &mut RgbImage, x: u32, y: u32, text: &str, color: Rgb<u8>) -> u32 {
+            let mut x = x;
+            for c in text.chars() {
+                let (ink, _) = crate::image_support::font_ink(
+                    &self.font,
+                    self.scale,
+                    &c.to_string(),
+                    self.threshold,
+                );
+                for (px, py) in ink.iter() {
+                    let (px, py) = (x as i32 + px, y as i32 + py);
+                    if px >= 0
+                        && py >= 0
+                        && (px as u32) < image.width()
+                        && (py as u32) < image.height()
+                    {
+                        *image.get_pixel_mut(px as u32, py as u32) = color;
+                    }
+                }
+                x += glyph_width(&self.glyph_set, &c.to_string());
+            }
+            x
+        }
+    }
+
+    fn glyph_width(glyph_set: &GlyphSet, glyph: &str) -> u32 {
+        glyph_set
+            .entries
+            .iter()
+            .find(|g| g.glyph() == glyph)
+            .map(|g| g.hist().len() as u32)
+            .unwrap_or_else(|| panic!("glyph {glyph:?} is not in the glyph set"))
+    }
+
+    /// Parameters of a synthetic screenshot.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct SyntheticConfig {
+        pub width: u32,
+        pub height: u32,
+        /// Probability that a text is placed at each free position on a line, between 0 and 1.
+        pub text_density: f32,
+        /// Number of distinct text colors, each color gets its own label.
+        pub color_count: usize,
+        /// Fraction of the background pixels that get a random color, between 0 and 1.
+        pub noise: f32,
+        /// Maximum number of words in a text, words are only combined if the glyph set has a space.
+        pub max_words: usize,
+        /// Seed of the random generator, the same seed produces the same image.
+        pub seed: u64,
+    }
+
+    impl Default for SyntheticConfig {
+        fn default() -> Self {
+            SyntheticConfig {
+                width: 640,
+                height: 480,
+                text_density: 0.5,
+                color_count: 2,
+                noise: 0.0,
+                max_words: 3,
+                seed: 1,
+            }
+        }
+    }
+
+    /// A text placed in the synthetic image.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct SyntheticText {
+        pub text: String,
+        pub color: Rgb<u8>,
+        /// The label of the color in [`SyntheticImage::labels`].
+        pub label: u32,
+        /// The area the text was rendered in, its height is the line height of the glyph set.
+        pub location: Rect,
+    }
+
+    /// A synthetic screenshot and its ground truth.
+    #[derive(Debug, Clone)]
+    pub struct SyntheticImage {
+        pub image: RgbImage,
+        /// One exact label for each text color.
+        pub labels: Vec<ColorLabel>,
+        /// The texts in the image, ordered by line and then from left to right.
+        pub texts: Vec<SyntheticText>,
+    }
+
+    impl SyntheticImage {
+        /// A collection annotating the texts and the image to evaluate it with, see
+        /// [`crate::evaluation::evaluate_images`].
+        pub fn evaluation_input(
+            &self,
+            file_path: &str,
+        ) -> (Collection, BTreeMap<PathBuf, RgbImage>) {
+            let collection = Collection {
+                images: self
+                    .texts
+                    .iter()
+                    .map(|t| AnnotatedImage {
+                        file_path: file_path.to_owned(),
+                        roi: t.location.clone(),
+                        text: t.text.clone(),
+                        color: (t.color.0[0], t.color.0[1], t.color.0[2]),
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            let images = [(PathBuf::from(file_path), self.image.clone())].into();
+            (collection, images)
+        }
     }
 
-    pub fn standard_alphabet() -> (RgbImage, &'static str) {
-        (render_standard_alphabet(), TEXT_STANDARD_ALPHABET)
+    /// Small xorshift generator, to be deterministic without extra dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn new(seed: u64) -> Self {
+            // The state may not be zero.
+            Rng(seed ^ 0x9e3779b97f4a7c15)
+        }
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+        fn chance(&mut self, p: f32) -> bool {
+            ((self.next() >> 40) as f32 / (1u64 << 24) as f32) < p
+        }
+    }
+
+    /// Evenly spread saturated colors.
+    fn palette(count: usize) -> Vec<Rgb<u8>> {
+        (0..count)
+            .map(|i| crate::color::hsv_to_rgb([i as f32 * 360.0 / count as f32, 1.0, 1.0]))
+            .collect()
+    }
+
+    /// Generate a screenshot with lines of random text in the label colors.
+    pub fn generate(renderer: &dyn SyntheticRenderer, config: &SyntheticConfig) -> SyntheticImage {
+        let mut rng = Rng::new(config.seed);
+        let glyph_set = renderer.glyph_set();
+        let colors = palette(config.color_count);
+        let labels = colors
+            .iter()
+            .enumerate()
+            .map(|(i, c)| ((*c).into(), i as u32))
+            .collect::<Vec<ColorLabel>>();
+
+        let mut image = RgbImage::new(config.width, config.height);
+        for p in image.pixels_mut() {
+            if rng.chance(config.noise) {
+                let v = rng.next();
+                let c = Rgb([(v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]);
+                // Keep a distance to the label colors, they should only come from text.
+                let near = colors
+                    .iter()
+                    .any(|l| l.0.iter().zip(c.0.iter()).all(|(a, b)| a.abs_diff(*b) < 32));
+                if !near {
+                    *p = c;
+                }
+            }
+        }
+
+        let letters = glyph_set
+            .entries
+            .iter()
+            .map(|g| g.glyph())
+            .filter(|g| g.chars().count() == 1 && !g.chars().all(char::is_whitespace))
+            .collect::<Vec<&str>>();
+        let has_space = glyph_set.entries.iter().any(|g| g.glyph() == " ");
+        let max_words = if has_space {
+            config.max_words.max(1)
+        } else {
+            1
+        };
+
+        let line_height = glyph_set.line_height;
+        let line_pitch = line_height + line_height / 2 + 1;
+        let gap = 2 * line_height;
+        let mut texts = vec![];
+        let mut y = line_height / 2;
+        while !letters.is_empty() && y + line_height <= config.height {
+            let mut x = rng.below(gap as usize + 1) as u32;
+            while x < config.width {
+                if !rng.chance(config.text_density) {
+                    x += gap;
+                    continue;
+                }
+                let words = (0..1 + rng.below(max_words))
+                    .map(|_| {
+                        (0..1 + rng.below(8))
+                            .map(|_| letters[rng.below(letters.len())])
+                            .collect::<String>()
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let width = renderer.width(&words);
+                if x + width > config.width {
+                    break;
+                }
+                let label = rng.below(colors.len().max(1));
+                let Some(color) = colors.get(label).copied() else {
+                    break;
+                };
+                renderer.render(&mut image, x, y, &words, color);
+                texts.push(SyntheticText {
+                    text: words,
+                    color,
+                    label: label as u32,
+                    location: Rect {
+                        x,
+                        y,
+                        w: width,
+                        h: line_height,
+                    },
+                });
+                x += width + gap;
+            }
+            y += line_pitch;
+        }
+
+        SyntheticImage {
+            image,
+            labels,
+            texts,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::binary::CompiledGlyphSet;
+    use synthetic::{generate, AlphabetRenderer, FontRenderer, SyntheticConfig, SyntheticRenderer};
+
+    #[test]
+    fn test_custom_alphabet() {
+        let alphabet = test_alphabet::Alphabet::new(3)
+            .with_space_width(2)
+            .with_letter(
+                'o',
+                "
+                xxx
+                x.x
+                xxx",
+            )
+            .with_letter(
+                'l',
+                "
+                x
+                x
+                x",
+            )
+            .with_letter(
+                '"',
+                "
+                x.x
+                x.x
+                ...",
+            );
+        assert_eq!(alphabet.letters(), "\"lo");
+        let (image, glyph_set) = alphabet.render_alphabet();
+        assert_eq!(image.dimensions(), (alphabet.width("\"lo"), 3));
+        assert_eq!(glyph_set.line_height, 3);
+        assert_eq!(glyph_set.entries.len(), 3);
+
+        let color = Rgb([0, 255, 0]);
+        let mut image = RgbImage::new(40, 9);
+        let end = alphabet.render(&mut image, 2, 3, "lol \"o\"", color);
+        assert_eq!(end, 2 + alphabet.width("lol \"o\""));
+        assert_eq!(*image.get_pixel(3, 3), color);
+        assert_eq!(*image.get_pixel(3, 2), Rgb([0, 0, 0]));
+
+        let matcher = crate::matcher::LongestGlyphMatcher::new(&glyph_set.entries);
+        let labels = [(color.into(), 0)];
+        let matches =
+            crate::moving_windowed_histogram(&image, glyph_set.line_height, &matcher, &labels)
+                .unwrap();
+        let found = matches.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        assert_eq!(found, ["lol", "\"o\""]);
+    }
+
+    #[test]
+    fn test_synthetic_standard() {
+        let renderer = AlphabetRenderer::standard();
+        let config = SyntheticConfig {
+            width: 320,
+            height: 200,
+            color_count: 3,
+            noise: 0.2,
+            ..Default::default()
+        };
+        let a = generate(&renderer, &config);
+        let b = generate(&renderer, &config);
+        assert_eq!(a.image, b.image);
+        assert_eq!(a.texts, b.texts);
+        assert!(a.texts.len() > 10);
+        assert_eq!(a.labels.len(), 3);
+
+        let (collection, images) = a.evaluation_input("synthetic.png");
+        let glyph_set = CompiledGlyphSet::new(renderer.glyph_set().clone()).unwrap();
+        let report = crate::evaluation::evaluate_images(&collection, &glyph_set, &images).unwrap();
+        assert_eq!(report.errors, 0, "{}", report.to_json());
+        assert!(report.extra.is_empty(), "{}", report.to_json());
+    }
+
+    #[test]
+    #[ignore = "needs the DejaVu Sans font in /usr/share/fonts/truetype/dejavu"]
+    fn test_synthetic_font() {
+        let path = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+        let data = std::fs::read(path).unwrap_or_else(|e| panic!("font {path} is required: {e}"));
+        let font = ab_glyph::FontVec::try_from_vec(data).unwrap();
+        let renderer = FontRenderer::new(font, 16.0, "acdehkmorstwxz ", 128);
+        let config = SyntheticConfig {
+            width: 400,
+            height: 200,
+            noise: 0.1,
+            seed: 3,
+            ..Default::default()
+        };
+        let synthetic = generate(&renderer, &config);
+        assert!(synthetic.texts.iter().any(|t| t.text.contains(' ')));
+        let (collection, images) = synthetic.evaluation_input("synthetic.png");
+        let glyph_set = CompiledGlyphSet::new(renderer.glyph_set().clone()).unwrap();
+        let report = crate::evaluation::evaluate_images(&collection, &glyph_set, &images).unwrap();
+        assert_eq!(report.errors, 0, "{}", report.to_json());
+        assert!(report.extra.is_empty(), "{}", report.to_json());
+    }
 
     #[test]
     fn draw_test_alphabet() {
@@ -0,0 +1,677 @@
+//! Building glyph sets from annotated images.
+//!
+//! A [`Collection`] describes images with a region of interest that holds a known text in a
+//! known color. Each region is converted into a histogram, which is split into the intervals of
+//! the individual characters to obtain the histogram of each glyph.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use image::{GenericImageView, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::glyphs::{Glyph, GlyphSet};
+use crate::image_support::image_to_histogram;
+use crate::matcher::LongestGlyphMatcher;
+use crate::{match_histogram_to_string, Error, Rect, Result};
+
+/// An image with a region of interest that holds the text in the provided color.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AnnotatedImage {
+    /// Path of the image, relative to the base directory of the collection.
+    pub file_path: String,
+    /// Region of the image that holds the text.
+    pub roi: Rect,
+    /// The text in the region.
+    pub text: String,
+    /// The color of the text.
+    pub color: (u8, u8, u8),
+}
+
+/// A collection of annotated images and the settings to build the glyph set from them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Collection {
+    /// The directory the image paths are relative to.
+    pub base_dir: Option<String>,
+    /// The annotated images.
+    pub images: Vec<AnnotatedImage>,
+
+    /// Add a zero bin at the start and end of each glyph. Ignored if estimate_bearings is set,
+    /// the glyphs are then padded with their estimated bearings instead.
+    #[serde(default)]
+    pub histogram_add_zero_start_end: bool,
+
+    /// Remove the spaces from the texts.
+    #[serde(default)]
+    pub drop_space: bool,
+
+    /// Add a space glyph of this many zero bins.
+    #[serde(default)]
+    pub space_min_size: Option<u8>,
+
+    /// Number of intervals in the histogram for characters that fall apart into multiple parts.
+    #[serde(default)]
+    pub char_intervals: HashMap<char, usize>,
+
+    /// Estimate the side bearings and the space width from the distances between the glyphs, the
+    /// glyphs are padded with their bearings instead of histogram_add_zero_start_end. The
+    /// estimated space width takes precedence over space_min_size. Glyphs can't overlap in the
+    /// histogram, so negative bearings of kerned pairs are clamped to zero, see
+    /// [`estimate_bearings`].
+    #[serde(default)]
+    pub estimate_bearings: bool,
+}
+
+impl Collection {
+    /// The path of the annotated image, taking the base directory into account.
+    pub fn image_path(&self, image: &AnnotatedImage) -> PathBuf {
+        if let Some(v) = self.base_dir.as_ref() {
+            Path::new(v).join(&image.file_path)
+        } else {
+            PathBuf::from(&image.file_path)
+        }
+    }
+}
+
+/// Load an image as rgb.
+pub(crate) fn load_image(path: &Path) -> Result<RgbImage> {
+    Ok(image::open(path)
+        .map_err(|source| Error::Image {
+            source,
+            path: Some(path.to_owned()),
+        })?
+        .to_rgb8())
+}
+
+/// The name used for an image in reports, its file name without extension.
+pub(crate) fn image_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|z| z.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Load a collection from a json or yaml file.
+pub fn load_collection(input_path: &Path) -> Result<Collection> {
+    let content = std::fs::read_to_string(input_path).map_err(|e| Error::io(e, input_path))?;
+    let extension = input_path.extension().unwrap_or_default();
+    if extension == "json" {
+        serde_json::from_str(&content).map_err(|e| Error::json(e, Some(input_path)))
+    } else if extension == "yaml" {
+        serde_yaml::from_str(&content).map_err(|e| Error::yaml(e, Some(input_path)))
+    } else {
+        Err(Error::UnsupportedExtension(input_path.to_owned()))
+    }
+}
+
+/// The histogram of the region of interest of an annotated image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    /// Name of the sample, used in reports.
+    pub name: String,
+    /// The text in the sample.
+    pub text: String,
+    /// The histogram of the pixels with the text color in the region of interest.
+    pub histogram: Vec<u8>,
+    /// The number of rows between the highest and lowest pixel with the text color.
+    pub height: u32,
+}
+
+impl Sample {
+    /// Create a sample from the region of interest in the image.
+    pub fn from_image(
+        image: &RgbImage,
+        annotated: &AnnotatedImage,
+        name: &str,
+        drop_space: bool,
+    ) -> Result<Sample> {
+        let invalid = |reason: &str| Error::InvalidAnnotation {
+            name: name.to_owned(),
+            reason: reason.to_owned(),
+        };
+        let roi = &annotated.roi;
+        if roi.x + roi.w > image.width() || roi.y + roi.h > image.height() {
+            return Err(invalid("region of interest is outside of the image"));
+        }
+
+        // Mask the region of interest with the color of the text.
+        let color = Rgb([annotated.color.0, annotated.color.1, annotated.color.2]);
+        let mut masked = image::GrayImage::new(roi.w, roi.h);
+        let mut rows: Option<(u32, u32)> = None;
+        for (x, y, p) in image.view(roi.x, roi.y, roi.w, roi.h).pixels() {
+            if p == color {
+                masked.put_pixel(x, y, image::Luma([255u8]));
+                rows = Some(rows.map(|(l, h)| (l.min(y), h.max(y))).unwrap_or((y, y)));
+            }
+        }
+        let (lowest, highest) = rows.ok_or_else(|| invalid("no pixels with the text color"))?;
+
+        let text = if drop_space {
+            annotated.text.replace(' ', "")
+        } else {
+            annotated.text.clone()
+        };
+        Ok(Sample {
+            name: name.to_owned(),
+            text,
+            histogram: image_to_histogram(&masked),
+            height: highest - lowest + 1,
+        })
+    }
+}
+
+/// Split the histogram into the interval of each character, as (start, end) with end exclusive.
+///
+/// Spaces don't have an interval, characters in intervals span that many non-zero intervals.
+pub fn splitter(
+    hist: &[u8],
+    chars: &[char],
+    intervals: &HashMap<char, usize>,
+) -> Vec<(usize, usize)> {
+    let chars = chars.iter().filter(|c| **c != ' ').collect::<Vec<_>>();
+    let mut v = vec![];
+    if chars.is_empty() {
+        return v;
+    }
+    let mut letter_start = None;
+    let mut s = None;
+    let mut ci = 0;
+    let mut intervals_this_letter = intervals.get(chars[ci]).cloned().unwrap_or(1);
+    for (i, a) in hist.iter().enumerate() {
+        if *a == 0 && s.is_some() {
+            intervals_this_letter -= 1;
+            s = None;
+            if intervals_this_letter == 0 {
+                v.push((letter_start.take().unwrap(), i));
+                ci += 1;
+                if ci < chars.len() {
+                    intervals_this_letter = intervals.get(chars[ci]).cloned().unwrap_or(1);
+                }
+            }
+        }
+        if *a != 0 && s.is_none() {
+            s = Some(i);
+            if letter_start.is_none() {
+                letter_start = Some(i);
+            }
+        }
+    }
+    v
+}
+
+/// A histogram observed for a character.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Observation {
+    /// The sample the histogram was observed in.
+    pub sample: String,
+    /// The histogram without side bearings.
+    pub histogram: Vec<u8>,
+}
+
+/// The text found in a sample by the created glyph set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleResult {
+    pub name: String,
+    pub expected: String,
+    pub found: String,
+}
+
+impl SampleResult {
+    /// Whether the found text is the expected text.
+    pub fn is_correct(&self) -> bool {
+        self.expected == self.found
+    }
+}
+
+/// Information collected while building a glyph set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrainingReport {
+    /// The histograms observed for each character.
+    pub observations: BTreeMap<char, Vec<Observation>>,
+    /// Distances between the glyphs in a pair of characters, spaces in between are included in
+    /// the key. Each entry holds the distance and the sample it was observed in.
+    pub bearings: BTreeMap<String, Vec<(usize, String)>>,
+    /// Characters that were observed with different histograms.
+    pub ambiguous: Vec<char>,
+    /// The result of matching the samples with the created glyph set.
+    pub results: Vec<SampleResult>,
+    /// The estimated side bearings, if they were estimated.
+    pub bearing_estimate: Option<BearingEstimate>,
+}
+
+/// Side bearings and space width estimated from the distances between glyphs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BearingEstimate {
+    /// Number of zero bins to add to the left of each glyph.
+    pub left: BTreeMap<char, u32>,
+    /// Number of zero bins to add to the right of each glyph.
+    pub right: BTreeMap<char, u32>,
+    /// Width of the space, if distances across spaces were observed.
+    pub space: Option<u32>,
+    /// Root mean square difference between the observed distances and the rounded estimate.
+    pub rms_error: f32,
+}
+
+/// Estimate the side bearings from the distances between glyphs, as collected in
+/// [`TrainingReport::bearings`].
+///
+/// Each observed distance is the right side bearing of the first glyph, plus the left side bearing
+/// of the second glyph, plus the space width for each space in between. These equations are solved
+/// in the least squares sense. The distances only determine the sum of the bearings of a pair, so
+/// a small ridge term picks one solution which is then shifted such that the smallest observed
+/// right side bearing is zero. This way the bearings are on the left side as much as possible,
+/// that side is stripped when matching the first glyph of a word.
+///
+/// The bearings and the space width are rounded to whole bins, negative values are clamped to
+/// zero. Pairs that are kerned closer together than their bearings allow then can't be matched
+/// as consecutive glyphs, this shows up in the rms_error of the estimate.
+pub fn estimate_bearings(bearings: &BTreeMap<String, Vec<(usize, String)>>) -> BearingEstimate {
+    let mut observations: Vec<(char, char, usize, f64)> = vec![];
+    for (key, entries) in bearings.iter() {
+        let chars = key.chars().collect::<Vec<char>>();
+        if chars.len() < 2 || chars[0] == ' ' || chars[chars.len() - 1] == ' ' {
+            continue;
+        }
+        let (first, last) = (chars[0], chars[chars.len() - 1]);
+        for (distance, _) in entries.iter() {
+            observations.push((first, last, chars.len() - 2, *distance as f64));
+        }
+    }
+
+    let chars = observations
+        .iter()
+        .flat_map(|(a, b, _, _)| [*a, *b])
+        .collect::<BTreeSet<char>>()
+        .into_iter()
+        .collect::<Vec<char>>();
+    let index = |c: char| chars.binary_search(&c).expect("all chars are present");
+    let has_space = observations.iter().any(|o| o.2 != 0);
+    // Unknowns are the left bearings, the right bearings and the space width.
+    let n = chars.len();
+    let unknowns = 2 * n + 1;
+    let row = |o: &(char, char, usize, f64)| {
+        let mut r = vec![0.0f64; unknowns];
+        r[n + index(o.0)] += 1.0;
+        r[index(o.1)] += 1.0;
+        r[2 * n] += o.2 as f64;
+        r
+    };
+
+    // Normal equations with the ridge term.
+    const RIDGE: f64 = 1e-3;
+    let mut ata = vec![vec![0.0f64; unknowns]; unknowns];
+    let mut atb = vec![0.0f64; unknowns];
+    for o in observations.iter() {
+        let r = row(o);
+        for i in 0..unknowns {
+            atb[i] += r[i] * o.3;
+            for j in 0..unknowns {
+                ata[i][j] += r[i] * r[j];
+            }
+        }
+    }
+    for (i, r) in ata.iter_mut().enumerate() {
+        r[i] += RIDGE;
+    }
+    let solution = solve_linear(ata, atb);
+
+    // Shift the solution along the direction the distances don't determine.
+    let observed_right = observations
+        .iter()
+        .map(|o| solution[n + index(o.0)])
+        .fold(f64::INFINITY, f64::min);
+    let shift = if observed_right.is_finite() {
+        observed_right
+    } else {
+        0.0
+    };
+    let to_bins = |v: f64| v.round().max(0.0) as u32;
+    let mut estimate = BearingEstimate {
+        space: has_space.then(|| to_bins(solution[2 * n])),
+        ..Default::default()
+    };
+    for (i, c) in chars.iter().enumerate() {
+        estimate.left.insert(*c, to_bins(solution[i] + shift));
+        estimate.right.insert(*c, to_bins(solution[n + i] - shift));
+    }
+
+    let squared_error = observations
+        .iter()
+        .map(|o| {
+            let predicted = estimate.right[&o.0] as f64
+                + estimate.left[&o.1] as f64
+                + (o.2 as u32 * estimate.space.unwrap_or(0)) as f64;
+            (predicted - o.3).powi(2)
+        })
+        .sum::<f64>();
+    estimate.rms_error =
+        (squared_error / std::cmp::max(observations.len(), 1) as f64).sqrt() as f32;
+    estimate
+}
+
+/// Solve the linear system with gaussian elimination, the matrix must be non singular.
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|x, y| a[*x][col].abs().total_cmp(&a[*y][col].abs()))
+            .expect("range is not empty");
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (upper, lower) = a.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (i, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (v, p) in row[col..].iter_mut().zip(pivot_row[col..].iter()) {
+                *v -= factor * p;
+            }
+            b[col + 1 + i] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0f64; n];
+    for r in (0..n).rev() {
+        let sum = (r + 1..n).map(|k| a[r][k] * x[k]).sum::<f64>();
+        x[r] = (b[r] - sum) / a[r][r];
+    }
+    x
+}
+
+/// Build a glyph set from a collection, the images are loaded from disk.
+pub fn build_glyph_set(collection: &Collection) -> Result<(GlyphSet, TrainingReport)> {
+    let mut samples = vec![];
+    for annotated in collection.images.iter() {
+        let path = collection.image_path(annotated);
+        let image = load_image(&path)?;
+        let name = image_name(&path);
+        samples.push(Sample::from_image(
+            &image,
+            annotated,
+            &name,
+            collection.drop_space,
+        )?);
+    }
+    build_glyph_set_from_samples(collection, &samples)
+}
+
+/// Build a glyph set from samples, using the settings of the collection.
+pub fn build_glyph_set_from_samples(
+    collection: &Collection,
+    samples: &[Sample],
+) -> Result<(GlyphSet, TrainingReport)> {
+    let mut report: TrainingReport = Default::default();
+
+    // For the white space / bearing we need to do some grouping, but we cannot observe the space
+    // itself.
+    /* Consider the letter A and R, with a space in between:
+
+          ^        |)
+         /_\       |\
+        /   \______| \
+        < c >< l  ><d>
+
+        let c be the width of A
+        l contains both the right side bearing of A, as well as the left side bearing of R.
+
+        If we obtain R right of something else, and A left of something else, we may be able to
+        determine the bearing from that, then we can subtract those with l and obtain the width
+        of the space character.
+    */
+    for sample in samples.iter() {
+        let name = &sample.name;
+        let histogram = &sample.histogram;
+        let chars = sample.text.chars().collect::<Vec<char>>();
+        let intervals = splitter(histogram, &chars, &collection.char_intervals);
+        let glyph_count = chars.iter().filter(|c| **c != ' ').count();
+        if intervals.len() < glyph_count {
+            return Err(Error::InvalidAnnotation {
+                name: name.clone(),
+                reason: format!(
+                    "found {} intervals in the histogram for {glyph_count} characters",
+                    intervals.len()
+                ),
+            });
+        }
+
+        let mut interval_pos = 0;
+        for (ci, c) in chars.iter().enumerate() {
+            if *c == ' ' {
+                // Space will not be in the intervals, so don't populate the histogram and
+                // don't advance the interval position.
+                continue;
+            }
+            let interval = &intervals[interval_pos];
+            // Collect left bearing.
+            if interval_pos != 0 && ci != 0 {
+                let mut left_bearing_start = ci - 1;
+                // Walk backwards through the spaces in the string at the current position.
+                while left_bearing_start != 0 && chars[left_bearing_start] == ' ' {
+                    left_bearing_start -= 1;
+                }
+                let key = chars[left_bearing_start..=ci].iter().collect::<String>();
+                let distance = interval.0 - intervals[interval_pos - 1].1;
+                report
+                    .bearings
+                    .entry(key)
+                    .or_default()
+                    .push((distance, name.clone()));
+            }
+            // Collect right bearing.
+            if (interval_pos + 1) < intervals.len() && (ci + 1) < chars.len() {
+                let mut right_bearing_end = ci + 1;
+                // Walk forwards through the spaces in the string at the current position.
+                while right_bearing_end + 1 < chars.len() && chars[right_bearing_end] == ' ' {
+                    right_bearing_end += 1;
+                }
+                let key = chars[ci..=right_bearing_end].iter().collect::<String>();
+                let distance = intervals[interval_pos + 1].0 - interval.1;
+                report
+                    .bearings
+                    .entry(key)
+                    .or_default()
+                    .push((distance, name.clone()));
+            }
+            report
+                .observations
+                .entry(*c)
+                .or_default()
+                .push(Observation {
+                    sample: name.clone(),
+                    histogram: histogram[interval.0..interval.1].to_vec(),
+                });
+            interval_pos += 1;
+        }
+    }
+
+    if collection.estimate_bearings {
+        report.bearing_estimate = Some(estimate_bearings(&report.bearings));
+    }
+
+    let mut glyph_set: GlyphSet = Default::default();
+    for (c, entries) in report.observations.iter() {
+        let histograms = entries
+            .iter()
+            .map(|o| &o.histogram)
+            .collect::<BTreeSet<_>>();
+        if histograms.len() > 1 {
+            report.ambiguous.push(*c);
+        }
+        for hist in histograms {
+            let mut padded_hist = hist.clone();
+            if let Some(estimate) = report.bearing_estimate.as_ref() {
+                let left = estimate.left.get(c).copied().unwrap_or(0) as usize;
+                let right = estimate.right.get(c).copied().unwrap_or(0) as usize;
+                padded_hist.splice(0..0, std::iter::repeat_n(0, left));
+                padded_hist.extend(std::iter::repeat_n(0, right));
+            } else if collection.histogram_add_zero_start_end {
+                padded_hist.insert(0, 0);
+                padded_hist.push(0);
+            }
+            glyph_set
+                .entries
+                .push(Glyph::new(&padded_hist, &format!("{c}")));
+        }
+    }
+
+    let space_size = report
+        .bearing_estimate
+        .as_ref()
+        .and_then(|e| e.space.map(|v| v.min(u8::MAX as u32) as u8))
+        .or(collection.space_min_size);
+    if let Some(space_min_size) = space_size {
+        let mut g = Glyph::new(&vec![0; space_min_size as usize], " ");
+        g.set_max_consecutive(Some(1));
+        g.set_trim_left(true);
+        g.set_trim_right(true);
+        g.set_ignore_on_lstrip(true);
+        glyph_set.entries.push(g);
+    }
+
+    glyph_set.line_height = samples.iter().map(|s| s.height).max().unwrap_or(0);
+    glyph_set.prepare();
+
+    // Check how well the glyph set recognises the samples.
+    let matcher = LongestGlyphMatcher::new(&glyph_set.entries);
+    for sample in samples.iter() {
+        report.results.push(SampleResult {
+            name: sample.name.clone(),
+            expected: sample.text.clone(),
+            found: match_histogram_to_string(&sample.histogram, &matcher),
+        });
+    }
+
+    Ok((glyph_set, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_alphabet::render_standard_color;
+
+    #[test]
+    fn test_build_glyph_set() {
+        let color = Rgb([200u8, 100, 50]);
+        let texts = ["abc", "ba dew", "caa"];
+        let mut image = RgbImage::new(100, 40);
+        let mut images = vec![];
+        for (i, text) in texts.iter().enumerate() {
+            let y = i as u32 * 10 + 2;
+            render_standard_color(&mut image, 3, y, text, color);
+            images.push(AnnotatedImage {
+                file_path: String::from("image.png"),
+                roi: Rect {
+                    x: 0,
+                    y: y - 1,
+                    w: 100,
+                    h: 9,
+                },
+                text: text.to_string(),
+                color: (200, 100, 50),
+            });
+        }
+        let collection = Collection {
+            images,
+            space_min_size: Some(5),
+            ..Default::default()
+        };
+        let samples = collection
+            .images
+            .iter()
+            .enumerate()
+            .map(|(i, a)| Sample::from_image(&image, a, &format!("{i}"), false).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(samples[0].histogram[..6], [0, 0, 0, 0, 5, 2]);
+
+        let (glyph_set, report) = build_glyph_set_from_samples(&collection, &samples).unwrap();
+        assert_eq!(glyph_set.line_height, 7);
+        let glyphs = glyph_set
+            .entries
+            .iter()
+            .map(|g| g.glyph())
+            .collect::<Vec<_>>();
+        assert_eq!(glyphs, vec!["a", "b", "c", "d", "e", "w", " "]);
+        assert_eq!(glyph_set.entries[0].hist(), &[5, 2, 2, 2, 5]);
+        assert!(report.ambiguous.is_empty());
+        assert_eq!(report.observations[&'a'].len(), 4);
+        // Letters are one pixel apart, a space adds another six. Each pair is seen from both sides.
+        assert_eq!(report.bearings["ab"], vec![(1, String::from("0")); 2]);
+        assert_eq!(report.bearings["a d"], vec![(7, String::from("1")); 2]);
+        for r in report.results.iter() {
+            assert!(r.is_correct(), "{r:?}");
+        }
+
+        // Annotations that don't agree with the image are reported.
+        let mut wrong = collection.images[0].clone();
+        wrong.text = String::from("abcd");
+        let sample = Sample::from_image(&image, &wrong, "wrong", false).unwrap();
+        let res = build_glyph_set_from_samples(&collection, &[sample]);
+        assert!(matches!(res, Err(Error::InvalidAnnotation { .. })));
+        wrong.color = (1, 2, 3);
+        let res = Sample::from_image(&image, &wrong, "wrong", false);
+        assert!(matches!(res, Err(Error::InvalidAnnotation { .. })));
+    }
+
+    #[test]
+    fn test_estimate_bearings() {
+        // Bearings of x, y and z are (left, right): (1, 0), (2, 1), (0, 3), space is 4 wide.
+        let mut bearings: BTreeMap<String, Vec<(usize, String)>> = Default::default();
+        for (key, distance) in [
+            ("xy", 2),
+            ("yz", 1),
+            ("zx", 4),
+            ("x z", 4),
+            ("zy", 5),
+            ("xx", 1),
+            ("yy", 3),
+            ("xz", 0),
+            ("yx", 2),
+        ] {
+            bearings.insert(key.to_owned(), vec![(distance, String::from("s"))]);
+        }
+        let estimate = estimate_bearings(&bearings);
+        assert_eq!(estimate.rms_error, 0.0);
+        assert_eq!(estimate.space, Some(4));
+        // Only the sums are observable, the smallest right bearing is zero.
+        assert_eq!(
+            estimate.left,
+            BTreeMap::from([('x', 1), ('y', 2), ('z', 0)])
+        );
+        assert_eq!(
+            estimate.right,
+            BTreeMap::from([('x', 0), ('y', 1), ('z', 3)])
+        );
+
+        // Build a glyph set with bearings from the test alphabet.
+        let color = Rgb([255u8, 255, 255]);
+        let mut image = RgbImage::new(150, 20);
+        render_standard_color(&mut image, 3, 2, "ba dew cab adwc", color);
+        let annotated = AnnotatedImage {
+            file_path: String::from("image.png"),
+            roi: Rect {
+                x: 0,
+                y: 0,
+                w: 150,
+                h: 20,
+            },
+            text: String::from("ba dew cab adwc"),
+            color: (255, 255, 255),
+        };
+        let sample = Sample::from_image(&image, &annotated, "sample", false).unwrap();
+        let collection = Collection {
+            images: vec![annotated],
+            estimate_bearings: true,
+            space_min_size: Some(2),
+            ..Default::default()
+        };
+        // The distances across the spaces can only be split into bearings and the space width if
+        // the bearings of those glyphs are observed without space in between too.
+        let (glyph_set, report) = build_glyph_set_from_samples(&collection, &[sample]).unwrap();
+        let estimate = report.bearing_estimate.unwrap();
+        assert_eq!(estimate.rms_error, 0.0);
+        assert_eq!(estimate.space, Some(6));
+        assert_eq!(glyph_set.entries[0].glyph(), "a");
+        assert_eq!(glyph_set.entries[0].hist(), &[0, 5, 2, 2, 2, 5]);
+        let space = glyph_set.entries.last().unwrap();
+        assert_eq!(space.glyph(), " ");
+        assert_eq!(space.hist().len(), 6);
+        assert!(report.results[0].is_correct(), "{:?}", report.results[0]);
+    }
+}
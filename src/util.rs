@@ -1,3 +1,4 @@
+use crate::error::{Error, Result};
 use crate::Match2D;
 use image::{GenericImageView, Pixel};
 use std::path::Path;
@@ -9,7 +10,7 @@ pub fn write_match_html<'a>(
     labels: &[crate::ColorLabel],
     image_path: &Path,
     out_path: &Path,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<()> {
     use std::fs::File;
     use std::io::Write;
     let mut c: String = String::new();
@@ -155,7 +156,8 @@ pub fn write_match_html<'a>(
     c.push_str(&"</svg>");
 
     let mut color_label_css: String = String::new();
-    for (c, label) in labels.iter() {
+    for (rule, label) in labels.iter() {
+        let c = rule.color();
         color_label_css.push_str(&format!(
             "svg .label_{l} {{
                 stroke: #{r:0>2x}{g:0>2x}{b:0>2x};
@@ -184,8 +186,9 @@ pub fn write_match_html<'a>(
     </body></html>",
     );
 
-    let mut file = File::create(out_path)?;
-    file.write(&c.as_bytes())?;
+    let mut file = File::create(out_path).map_err(|e| Error::io(e, out_path))?;
+    file.write_all(c.as_bytes())
+        .map_err(|e| Error::io(e, out_path))?;
     Ok(())
 }
 
@@ -222,11 +225,11 @@ where
     c
 }
 
-pub fn parse_json_labels(data: &str) -> serde_json::Result<Vec<(image::Rgb<u8>, u32)>> {
-    let mut res: Vec<(image::Rgb<u8>, u32)> = vec![];
-    let v: Vec<(u8, u8, u8, u32)> = serde_json::from_str(data)?;
+pub fn parse_json_labels(data: &str) -> Result<Vec<crate::ColorLabel>> {
+    let mut res: Vec<crate::ColorLabel> = vec![];
+    let v: Vec<(u8, u8, u8, u32)> = serde_json::from_str(data).map_err(|e| Error::json(e, None))?;
     for r in v {
-        res.push((image::Rgb::<u8>([r.0, r.1, r.2]), r.3));
+        res.push((image::Rgb::<u8>([r.0, r.1, r.2]).into(), r.3));
     }
     Ok(res)
 }